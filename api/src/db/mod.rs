@@ -1,8 +1,37 @@
+use axum::extract::FromRef;
+use indexer::storage::time_scale::TimescaleStorage;
 use sqlx::postgres::PgPoolOptions;
-use std::env;
+use std::{env, sync::Arc, time::Duration};
 
 pub type Db = sqlx::Pool<sqlx::Postgres>;
 
+/// **Route state: the pool for the API's own queries and the indexer storage for the shared ones**
+#[derive(Clone)]
+pub struct ApiState {
+    pub db: Db,
+    pub storage: Arc<TimescaleStorage>,
+}
+
+impl ApiState {
+    pub fn new(db: Db) -> Self {
+        // The API only reads, so the storage's user op writer stays idle
+        let storage = TimescaleStorage::from_pool(db.clone(), Duration::ZERO, 1);
+        Self { db, storage: Arc::new(storage) }
+    }
+}
+
+impl FromRef<ApiState> for Db {
+    fn from_ref(state: &ApiState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<ApiState> for Arc<TimescaleStorage> {
+    fn from_ref(state: &ApiState) -> Self {
+        Arc::clone(&state.storage)
+    }
+}
+
 pub async fn connect() -> Result<Db, sqlx::Error> {
     let db_url = env::var("TIMESCALE_DB_URL")
         .expect("TIMESCALE_DB_URL must be set in .env");
@@ -2,8 +2,7 @@ use std::env;
 
 use axum::{Router, routing::get};
 use dotenv::dotenv;
-use tracing_subscriber;
-use crate::routes::{get_user_op, health_check};
+use crate::routes::{get_spend, get_user_balance_history, get_user_net_charge, get_user_op, health_check};
 use tokio::net::TcpListener;
 
 mod db;
@@ -16,11 +15,15 @@ async fn main() {
     tracing_subscriber::fmt::init();
 
     let db = db::connect().await.expect("❌ DB connection failed");
+    let state = db::ApiState::new(db);
 
     let app = Router::new()
     .route("/user_op/:hash", get(get_user_op))
+    .route("/user/:address/net_charge", get(get_user_net_charge))
+    .route("/user/:address/balance_history", get(get_user_balance_history))
+    .route("/spend/:interval", get(get_spend))
     .route("/health", get(health_check))
-    .with_state(db);
+    .with_state(state);
 
     // 👇 Read from environment variables
     let host = env::var("API_HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
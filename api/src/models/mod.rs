@@ -2,6 +2,7 @@
 use serde::{Serialize, Deserialize};
 use sqlx::FromRow;
 use chrono::{DateTime, Utc, NaiveDateTime};
+use indexer::model::spend::SpendBucket;
 
 #[derive(Serialize, Deserialize, Debug, FromRow)]
 pub struct UserOperationRecord {
@@ -16,4 +17,100 @@ pub struct UserOperationRecord {
     pub token: Option<String>,
     pub metadata: Option<serde_json::Value>,
     pub updated_at: NaiveDateTime,
+    #[sqlx(skip)]
+    pub revert_reasons: Vec<RevertReasonRecord>,
+    #[sqlx(skip)]
+    pub net_charge: Option<UserOpNetChargeRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, FromRow)]
+pub struct RevertReasonRecord {
+    pub stage: String,
+    pub reason_type: String,
+    pub reason: Option<String>,
+    pub selector: Option<String>,
+    pub revert_data: String,
+}
+
+// Amounts are NUMERIC in wei, returned as strings
+#[derive(Serialize, Deserialize, Debug, FromRow)]
+pub struct UserOpNetChargeRecord {
+    pub deducted_amount: String,
+    pub refunded_amount: String,
+    pub net_charge: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, FromRow)]
+pub struct UserNetChargeRecord {
+    pub chain_id: i32,
+    pub user_address: String,
+    pub user_ops: i64,
+    pub deducted_amount: String,
+    pub refunded_amount: String,
+    pub net_charge: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, FromRow)]
+pub struct BalanceHistoryRecord {
+    pub time: DateTime<Utc>,
+    pub chain_id: i32,
+    pub paymaster: String,
+    pub delta: String,
+    pub balance: String, // running balance since the first indexed movement
+    pub reason: String,
+    pub user_op_hash: Option<String>,
+    pub block_number: i64,
+    pub tx_hash: String,
+    pub log_index: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BalanceHistoryQuery {
+    pub chain_id: Option<i32>,
+    pub paymaster: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SpendRecord {
+    pub bucket: DateTime<Utc>,
+    pub chain_id: i32,
+    pub paymaster_mode: Option<String>,
+    pub paymaster_id: Option<String>,
+    pub org_id: Option<String>,
+    pub policy_id: Option<String>,
+    pub ops: i64,
+    pub failed_ops: i64,
+    pub gas_used: Option<String>,
+    pub native_cost_wei: Option<String>,
+    pub usd_amount: Option<String>,
+}
+
+impl From<SpendBucket> for SpendRecord {
+    fn from(bucket: SpendBucket) -> Self {
+        Self {
+            bucket: bucket.bucket,
+            chain_id: bucket.chain_id,
+            paymaster_mode: bucket.paymaster_mode,
+            paymaster_id: bucket.paymaster_id,
+            org_id: bucket.org_id,
+            policy_id: bucket.policy_id,
+            ops: bucket.ops,
+            failed_ops: bucket.failed_ops,
+            gas_used: bucket.gas_used.map(|v| v.to_string()),
+            native_cost_wei: bucket.native_cost_wei.map(|v| v.to_string()),
+            usd_amount: bucket.usd_amount.map(|v| v.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SpendQuery {
+    pub from: Option<DateTime<Utc>>, // defaults to one day (hourly) or 30 days (daily) before `to`
+    pub to: Option<DateTime<Utc>>,   // defaults to now
+    pub chain_id: Option<i32>,
+    pub paymaster_mode: Option<String>,
+    pub paymaster_id: Option<String>,
+    pub org_id: Option<String>,
+    pub policy_id: Option<String>,
 }
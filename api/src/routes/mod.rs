@@ -1,7 +1,10 @@
-use axum::{extract::{Path, State}, Json};
+use axum::{extract::{Path, Query, State}, Json};
 use axum::http::StatusCode;
 use crate::db::Db;
-use crate::models::UserOperationRecord;
+use indexer::model::spend::{self, SpendInterval};
+use indexer::storage::{time_scale::TimescaleStorage, Storage};
+use std::sync::Arc;
+use crate::models::{BalanceHistoryQuery, BalanceHistoryRecord, RevertReasonRecord, SpendQuery, SpendRecord, UserNetChargeRecord, UserOpNetChargeRecord, UserOperationRecord};
 
 pub async fn get_user_op(
     Path(user_op_hash): Path<String>,
@@ -20,8 +23,31 @@ pub async fn get_user_op(
     tracing::info!("🔍 Query result: {:?}", query_result);
 
     match query_result {
-        Ok(record) => {
+        Ok(mut record) => {
             tracing::info!("✅ Found record for hash: {}", user_op_hash);
+            record.revert_reasons = sqlx::query_as::<_, RevertReasonRecord>(
+                "SELECT stage, reason_type, reason, selector, revert_data \
+                 FROM user_op_revert_reasons WHERE user_op_hash = $1 ORDER BY stage"
+            )
+            .bind(user_op_hash)
+            .fetch_all(&db)
+            .await
+            .map_err(|e| {
+                tracing::error!("❌ DB error while fetching revert reasons of {}: {:?}", user_op_hash, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            // Only prepaid ops have a deduction to net refunds against
+            record.net_charge = sqlx::query_as::<_, UserOpNetChargeRecord>(
+                "SELECT deducted_amount::TEXT, refunded_amount::TEXT, net_charge::TEXT \
+                 FROM user_op_net_charges WHERE user_op_hash = $1"
+            )
+            .bind(user_op_hash)
+            .fetch_optional(&db)
+            .await
+            .map_err(|e| {
+                tracing::error!("❌ DB error while fetching net charge of {}: {:?}", user_op_hash, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
             Ok(Json(record))
         },
         Err(sqlx::Error::RowNotFound) => {
@@ -35,6 +61,94 @@ pub async fn get_user_op(
     }
 }
 
+pub async fn get_user_net_charge(
+    Path(user_address): Path<String>,
+    State(db): State<Db>,
+) -> Result<Json<Vec<UserNetChargeRecord>>, StatusCode> {
+    let user_address = user_address.trim().to_lowercase();
+    tracing::info!("🔍 Fetching net charge of user: {}", user_address);
+
+    // One row per chain the user was charged or refunded on
+    sqlx::query_as::<_, UserNetChargeRecord>(
+        "SELECT chain_id, user_address, user_ops, deducted_amount::TEXT, refunded_amount::TEXT, net_charge::TEXT \
+         FROM user_net_charges WHERE user_address = $1 ORDER BY chain_id"
+    )
+    .bind(&user_address)
+    .fetch_all(&db)
+    .await
+    .map(Json)
+    .map_err(|e| {
+        tracing::error!("❌ DB error while fetching net charge of {}: {:?}", user_address, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn get_user_balance_history(
+    Path(user_address): Path<String>,
+    Query(query): Query<BalanceHistoryQuery>,
+    State(db): State<Db>,
+) -> Result<Json<Vec<BalanceHistoryRecord>>, StatusCode> {
+    let user_address = user_address.trim().to_lowercase();
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    tracing::info!("🔍 Fetching prepaid balance history of user: {}", user_address);
+
+    // Newest first, the running balance of each row covers everything before it
+    sqlx::query_as::<_, BalanceHistoryRecord>(
+        "SELECT time, chain_id, paymaster, delta::TEXT, balance::TEXT, reason, user_op_hash, block_number, tx_hash, log_index \
+         FROM prepaid_balance_history \
+         WHERE user_address = $1 AND ($2::INTEGER IS NULL OR chain_id = $2) \
+           AND ($3::TEXT IS NULL OR LOWER(paymaster) = LOWER($3)) \
+         ORDER BY block_number DESC, log_index DESC \
+         LIMIT $4"
+    )
+    .bind(&user_address)
+    .bind(query.chain_id)
+    .bind(query.paymaster.as_deref().map(str::trim))
+    .bind(limit)
+    .fetch_all(&db)
+    .await
+    .map(Json)
+    .map_err(|e| {
+        tracing::error!("❌ DB error while fetching balance history of {}: {:?}", user_address, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn get_spend(
+    Path(interval): Path<String>,
+    Query(query): Query<SpendQuery>,
+    State(storage): State<Arc<TimescaleStorage>>,
+) -> Result<Json<Vec<SpendRecord>>, StatusCode> {
+    let (spend_interval, default_span) = match interval.as_str() {
+        "hourly" => (SpendInterval::Hour, chrono::Duration::days(1)),
+        "daily" => (SpendInterval::Day, chrono::Duration::days(30)),
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    let chain_id = query.chain_id.map(u32::try_from).transpose().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let to = query.to.unwrap_or_else(chrono::Utc::now);
+    let from = query.from.unwrap_or(to - default_span);
+    tracing::info!("🔍 Fetching {} spend from {} to {}", interval, from, to);
+
+    // Served from the continuous aggregates by the indexer's storage query, the latest buckets are computed on read
+    storage
+        .get_spend(spend::SpendQuery {
+            interval: spend_interval,
+            from,
+            to,
+            chain_id,
+            paymaster_mode: query.paymaster_mode,
+            paymaster_id: query.paymaster_id,
+            org_id: query.org_id,
+            policy_id: query.policy_id,
+        })
+        .await
+        .map(|buckets| Json(buckets.into_iter().map(SpendRecord::from).collect()))
+        .map_err(|e| {
+            tracing::error!("❌ DB error while fetching {} spend: {:?}", interval, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
 pub async fn health_check() -> (StatusCode, Json<&'static str>) {
     (StatusCode::OK, Json("OK"))
 }
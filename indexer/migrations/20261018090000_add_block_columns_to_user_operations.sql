@@ -0,0 +1,7 @@
+-- Block the user operation was included in, used to detect and roll back chain reorgs
+ALTER TABLE pm_user_operations
+    ADD COLUMN IF NOT EXISTS block_number BIGINT,
+    ADD COLUMN IF NOT EXISTS block_hash CHAR(66);
+
+CREATE INDEX IF NOT EXISTS idx_chain_id_block_number
+  ON pm_user_operations(chain_id, block_number);
@@ -0,0 +1,10 @@
+-- Bundle transaction that included the user operation
+ALTER TABLE pm_user_operations
+    ADD COLUMN IF NOT EXISTS tx_hash CHAR(66),
+    ADD COLUMN IF NOT EXISTS log_index INTEGER,
+    ADD COLUMN IF NOT EXISTS bundler CHAR(42),              -- Sender of the bundle transaction
+    ADD COLUMN IF NOT EXISTS beneficiary CHAR(42),          -- Receiver of the bundle compensation
+    ADD COLUMN IF NOT EXISTS effective_gas_price NUMERIC;   -- Gas price paid by the bundle, in wei
+
+CREATE INDEX IF NOT EXISTS idx_tx_hash
+  ON pm_user_operations(tx_hash);
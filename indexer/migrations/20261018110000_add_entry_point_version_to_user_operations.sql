@@ -0,0 +1,3 @@
+-- EntryPoint release that executed the user operation ("0.6", "0.7" or "0.8")
+ALTER TABLE pm_user_operations
+    ADD COLUMN IF NOT EXISTS entry_point_version VARCHAR(8);
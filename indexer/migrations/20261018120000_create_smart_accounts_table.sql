@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS smart_accounts (
+    chain_id INTEGER NOT NULL,
+    sender CHAR(42) NOT NULL,                -- Deployed account address
+    factory CHAR(42) NOT NULL,
+    user_op_hash CHAR(66) NOT NULL,          -- User op that deployed the account
+    paymaster CHAR(42) NOT NULL,             -- Paymaster that sponsored the deployment
+
+    -- First time the indexer saw the deployment
+    first_seen_block BIGINT NOT NULL,
+    first_seen_at TIMESTAMPTZ NOT NULL,
+    tx_hash CHAR(66),
+
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain_id, sender)
+);
+
+-- Used for new accounts per day reporting
+CREATE INDEX IF NOT EXISTS idx_smart_accounts_first_seen_at
+  ON smart_accounts(first_seen_at);
+
+CREATE INDEX IF NOT EXISTS idx_smart_accounts_user_op_hash
+  ON smart_accounts(user_op_hash);
+
+CREATE INDEX IF NOT EXISTS idx_smart_accounts_paymaster
+  ON smart_accounts(chain_id, paymaster);
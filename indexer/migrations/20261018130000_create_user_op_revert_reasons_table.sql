@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS user_op_revert_reasons (
+    time TIMESTAMPTZ NOT NULL,               -- Block time of the revert
+    chain_id INTEGER NOT NULL,
+    user_op_hash CHAR(66) NOT NULL,
+    stage VARCHAR(10) NOT NULL,              -- execution or postOp
+
+    sender CHAR(42) NOT NULL,
+    nonce NUMERIC NOT NULL,
+
+    -- Decoded reason
+    reason_type VARCHAR(10) NOT NULL,        -- Error, Panic, Custom or Unknown
+    reason TEXT,                             -- Error(string) message or Panic description
+    selector CHAR(10),                       -- 4-byte error selector
+    revert_data TEXT NOT NULL,               -- Raw revert bytes
+
+    block_number BIGINT,
+    tx_hash CHAR(66),
+
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain_id, user_op_hash, stage)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_op_revert_reasons_user_op_hash
+  ON user_op_revert_reasons(user_op_hash);
+
+CREATE INDEX IF NOT EXISTS idx_user_op_revert_reasons_selector
+  ON user_op_revert_reasons(selector);
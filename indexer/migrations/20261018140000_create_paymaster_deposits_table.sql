@@ -0,0 +1,24 @@
+-- Running EntryPoint deposit and stake of our paymasters
+CREATE TABLE IF NOT EXISTS paymaster_deposits (
+    chain_id INTEGER NOT NULL,
+    entry_point CHAR(42) NOT NULL,
+    paymaster CHAR(42) NOT NULL,
+
+    deposit NUMERIC,                         -- In wei, NULL until the first Deposited event or balanceOf check
+    stake NUMERIC,                           -- In wei
+    unstake_delay_sec BIGINT,
+    withdraw_time BIGINT,                    -- Unix time the stake can be withdrawn, 0 while locked
+
+    -- Last change applied, older logs (e.g. from a backfill) are ignored
+    last_block BIGINT NOT NULL DEFAULT 0,
+    last_log_index BIGINT NOT NULL DEFAULT 0,
+    reconciled_at TIMESTAMPTZ,               -- Last balanceOf check
+
+    updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain_id, entry_point, paymaster)
+);
+
+CREATE TRIGGER paymaster_deposits_set_updated_at
+BEFORE UPDATE ON paymaster_deposits
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_set_updated_at();
@@ -0,0 +1,14 @@
+-- Last fully stored block per chain and indexing cursor, Redis only caches the live one
+CREATE TABLE IF NOT EXISTS indexer_checkpoints (
+    chain_id INTEGER NOT NULL,
+    cursor VARCHAR(128) NOT NULL,            -- live, or backfill:{job}
+    block_number BIGINT NOT NULL,
+
+    updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain_id, cursor)
+);
+
+CREATE TRIGGER indexer_checkpoints_set_updated_at
+BEFORE UPDATE ON indexer_checkpoints
+FOR EACH ROW
+EXECUTE PROCEDURE trigger_set_updated_at();
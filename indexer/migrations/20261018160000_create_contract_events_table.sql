@@ -0,0 +1,29 @@
+-- Logs of events configured with a full ABI, decoded generically
+CREATE TABLE IF NOT EXISTS contract_events (
+    time TIMESTAMPTZ NOT NULL,               -- Block time of the log
+    chain_id INTEGER NOT NULL,
+    contract_address CHAR(42) NOT NULL,
+    event_name VARCHAR(128) NOT NULL,
+    event_signature CHAR(66) NOT NULL,       -- topic0
+
+    block_number BIGINT NOT NULL,
+    block_hash CHAR(66),
+    tx_hash CHAR(66),
+    log_index INTEGER NOT NULL,
+
+    args JSONB NOT NULL,                     -- Decoded parameters by name, integers as strings
+
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain_id, block_number, log_index, time)
+);
+
+SELECT create_hypertable('contract_events', by_range('time'));
+
+CREATE INDEX IF NOT EXISTS idx_contract_events_contract_event
+  ON contract_events(chain_id, contract_address, event_name, time DESC);
+
+CREATE INDEX IF NOT EXISTS idx_contract_events_tx_hash
+  ON contract_events(tx_hash);
+
+CREATE INDEX IF NOT EXISTS idx_contract_events_args
+  ON contract_events USING GIN (args);
@@ -0,0 +1,79 @@
+-- Prepaid sponsorships, from the paymaster UserOperationSponsored event
+CREATE TABLE IF NOT EXISTS user_op_sponsorships (
+    time TIMESTAMPTZ NOT NULL,               -- Block time of the log
+    chain_id INTEGER NOT NULL,
+    user_op_hash CHAR(66) NOT NULL,
+    user_address CHAR(42) NOT NULL,          -- Sponsored user
+    paymaster CHAR(42) NOT NULL,
+
+    block_number BIGINT NOT NULL,
+    tx_hash CHAR(66),
+    log_index INTEGER NOT NULL,
+
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain_id, user_op_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_op_sponsorships_user
+  ON user_op_sponsorships(chain_id, LOWER(user_address));
+
+-- Prepaid balance refunds, from the paymaster RefundProcessed event
+CREATE TABLE IF NOT EXISTS paymaster_refunds (
+    time TIMESTAMPTZ NOT NULL,               -- Block time of the log
+    chain_id INTEGER NOT NULL,
+    paymaster CHAR(42) NOT NULL,
+    user_address CHAR(42) NOT NULL,          -- Refunded user
+    amount NUMERIC NOT NULL,
+    user_op_hash CHAR(66),                   -- Op whose deduction is refunded, NULL when refunded outside a bundle
+
+    block_number BIGINT NOT NULL,
+    tx_hash CHAR(66) NOT NULL,
+    log_index INTEGER NOT NULL,
+
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain_id, tx_hash, log_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_paymaster_refunds_user
+  ON paymaster_refunds(chain_id, LOWER(user_address));
+
+CREATE INDEX IF NOT EXISTS idx_paymaster_refunds_user_op_hash
+  ON paymaster_refunds(chain_id, user_op_hash);
+
+-- Deduction of each prepaid op minus the refunds linked to it
+CREATE OR REPLACE VIEW user_op_net_charges AS
+SELECT
+    op.chain_id,
+    op.user_op_hash,
+    LOWER(op.deducted_user) AS user_address,
+    op.deducted_amount,
+    COALESCE(SUM(r.amount), 0) AS refunded_amount,
+    op.deducted_amount - COALESCE(SUM(r.amount), 0) AS net_charge
+FROM pm_user_operations op
+LEFT JOIN paymaster_refunds r
+  ON r.chain_id = op.chain_id AND r.user_op_hash = op.user_op_hash
+WHERE op.deducted_amount IS NOT NULL
+GROUP BY op.chain_id, op.user_op_hash, op.deducted_user, op.deducted_amount;
+
+-- Everything deducted from a user minus everything refunded to them, linked to an op or not
+CREATE OR REPLACE VIEW user_net_charges AS
+WITH deducted AS (
+    SELECT chain_id, LOWER(deducted_user) AS user_address, SUM(deducted_amount) AS amount, COUNT(*) AS user_ops
+    FROM pm_user_operations
+    WHERE deducted_user IS NOT NULL AND deducted_amount IS NOT NULL
+    GROUP BY chain_id, LOWER(deducted_user)
+), refunded AS (
+    SELECT chain_id, LOWER(user_address) AS user_address, SUM(amount) AS amount
+    FROM paymaster_refunds
+    GROUP BY chain_id, LOWER(user_address)
+)
+SELECT
+    COALESCE(d.chain_id, r.chain_id) AS chain_id,
+    COALESCE(d.user_address, r.user_address) AS user_address,
+    COALESCE(d.user_ops, 0) AS user_ops,
+    COALESCE(d.amount, 0) AS deducted_amount,
+    COALESCE(r.amount, 0) AS refunded_amount,
+    COALESCE(d.amount, 0) - COALESCE(r.amount, 0) AS net_charge
+FROM deducted d
+FULL OUTER JOIN refunded r
+  ON r.chain_id = d.chain_id AND r.user_address = d.user_address;
@@ -0,0 +1,45 @@
+-- Double-entry ledger of prepaid gas balances: every deduction or refund is one row for the user
+-- and one for the paymaster with the opposite sign, so the deltas of a log always sum to zero
+CREATE TABLE IF NOT EXISTS prepaid_gas_ledger (
+    time TIMESTAMPTZ NOT NULL,               -- Block time of the log
+    chain_id INTEGER NOT NULL,
+    paymaster CHAR(42) NOT NULL,
+    user_address CHAR(42) NOT NULL,
+    account VARCHAR(10) NOT NULL,            -- user or paymaster, the side this row books
+    delta NUMERIC NOT NULL,                  -- Signed change of the account, in wei
+    reason VARCHAR(16) NOT NULL,             -- deduction or refund
+    user_op_hash CHAR(66),                   -- Op the movement belongs to, once its UserOperationEvent is seen
+
+    block_number BIGINT NOT NULL,
+    tx_hash CHAR(66) NOT NULL,
+    log_index INTEGER NOT NULL,
+
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain_id, tx_hash, log_index, account)
+);
+
+CREATE INDEX IF NOT EXISTS idx_prepaid_gas_ledger_user
+  ON prepaid_gas_ledger(chain_id, LOWER(user_address), block_number, log_index);
+
+CREATE INDEX IF NOT EXISTS idx_prepaid_gas_ledger_user_op_hash
+  ON prepaid_gas_ledger(user_op_hash);
+
+-- User side of the ledger with the running balance, relative to the first indexed movement
+CREATE OR REPLACE VIEW prepaid_balance_history AS
+SELECT
+    time,
+    chain_id,
+    paymaster,
+    LOWER(user_address) AS user_address,
+    delta,
+    SUM(delta) OVER (
+        PARTITION BY chain_id, paymaster, LOWER(user_address)
+        ORDER BY block_number, log_index
+    ) AS balance,
+    reason,
+    user_op_hash,
+    block_number,
+    tx_hash,
+    log_index
+FROM prepaid_gas_ledger
+WHERE account = 'user';
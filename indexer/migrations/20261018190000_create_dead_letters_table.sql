@@ -0,0 +1,23 @@
+-- Inputs the indexer failed to decode or store, kept for replay once fixed
+CREATE TABLE IF NOT EXISTS dead_letters (
+    id BIGSERIAL PRIMARY KEY,
+    source VARCHAR(8) NOT NULL,              -- log or kafka
+    entry_key VARCHAR(256) NOT NULL,         -- chain:tx:log_index or topic:partition:offset
+    chain_id INTEGER,
+    block_number BIGINT,
+    log_index INTEGER,
+
+    stage VARCHAR(16) NOT NULL,              -- decode, parse or store
+    error TEXT NOT NULL,                     -- Last error
+    payload TEXT NOT NULL,                   -- Raw log as JSON, or the Kafka payload
+    attempts INTEGER NOT NULL DEFAULT 1,
+
+    first_failed_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    last_failed_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    resolved_at TIMESTAMPTZ,                 -- Set once a replay succeeded
+    UNIQUE (source, entry_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_dead_letters_unresolved
+  ON dead_letters(source, chain_id, block_number, log_index)
+  WHERE resolved_at IS NULL;
@@ -0,0 +1,38 @@
+-- Mirrors Status::priority: on conflict, a write with a higher status overwrites the op, any other only fills in
+CREATE OR REPLACE FUNCTION user_op_status_priority(status TEXT)
+RETURNS INTEGER
+LANGUAGE SQL IMMUTABLE
+AS $$
+  SELECT CASE UPPER(status)
+    WHEN 'FAILED' THEN 3
+    WHEN 'SUCCESS' THEN 2
+    WHEN 'ELIGIBLE' THEN 1
+    ELSE 0
+  END
+$$;
+
+-- One row per user op, pinning the hypertable time of its pm_user_operations row.
+-- A hypertable cannot be unique on (chain_id, user_op_hash) alone, with the time pinned
+-- idx_user_chain_id_user_op_hash (chain_id, user_op_hash, time) is unique per op and backs ON CONFLICT.
+CREATE TABLE IF NOT EXISTS user_op_keys (
+    chain_id INTEGER NOT NULL,
+    user_op_hash CHAR(66) NOT NULL,
+    time TIMESTAMPTZ NOT NULL,               -- time of the op's pm_user_operations row
+    PRIMARY KEY (chain_id, user_op_hash)
+);
+
+-- Racing writers may have stored an op twice, keep its highest status row, then the last updated one
+DELETE FROM pm_user_operations p
+USING (
+    SELECT chain_id, user_op_hash, time,
+           ROW_NUMBER() OVER (
+               PARTITION BY chain_id, user_op_hash
+               ORDER BY user_op_status_priority(status) DESC, updated_at DESC NULLS LAST, time
+           ) AS rank
+    FROM pm_user_operations
+) d
+WHERE p.chain_id = d.chain_id AND p.user_op_hash = d.user_op_hash AND p.time = d.time AND d.rank > 1;
+
+INSERT INTO user_op_keys (chain_id, user_op_hash, time)
+SELECT chain_id, user_op_hash, time FROM pm_user_operations
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,22 @@
+-- Gas cost and gas used are uint256 on chain, BIGINT overflowed on large costs
+ALTER TABLE pm_user_operations
+    ALTER COLUMN actual_gas_cost TYPE NUMERIC(78, 0),   -- Cost in wei
+    ALTER COLUMN actual_gas_used TYPE NUMERIC(78, 0);   -- Gas used
+
+-- Mined ops whose values did not fit BIGINT were stored without them, metadata still has them
+UPDATE pm_user_operations
+SET actual_gas_cost = (metadata->>'actualGasCost')::NUMERIC
+WHERE actual_gas_cost IS NULL
+  AND UPPER(status) IN ('SUCCESS', 'FAILED')
+  AND metadata->>'actualGasCost' ~ '^[0-9]{1,78}$';
+
+UPDATE pm_user_operations
+SET actual_gas_used = (metadata->>'actualGasUsed')::NUMERIC
+WHERE actual_gas_used IS NULL
+  AND UPPER(status) IN ('SUCCESS', 'FAILED')
+  AND metadata->>'actualGasUsed' ~ '^[0-9]{1,78}$';
+
+-- USD amounts were computed in f64, recompute them exactly: 1 wei is 10^-18 of the native token
+UPDATE pm_user_operations
+SET usd_amount = actual_gas_cost * native_usd_price * 1e-18
+WHERE actual_gas_cost IS NOT NULL AND native_usd_price IS NOT NULL;
@@ -0,0 +1,62 @@
+-- Sponsorship policy of the op, from the paymaster service
+ALTER TABLE pm_user_operations
+    ADD COLUMN IF NOT EXISTS policy_id VARCHAR(64);
+
+-- Spend of mined ops per hour. Created without data, as materializing cannot run in a migration transaction:
+-- the indexer's aggregate refresh task fills in history, the refresh policies below keep recent buckets current.
+CREATE MATERIALIZED VIEW IF NOT EXISTS user_op_spend_hourly
+WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+SELECT
+    time_bucket(INTERVAL '1 hour', time) AS bucket,
+    chain_id,
+    paymaster_mode,
+    paymaster_id,
+    org_id,
+    policy_id,
+    COUNT(*) AS ops,
+    COUNT(*) FILTER (WHERE UPPER(status) = 'FAILED') AS failed_ops,
+    SUM(actual_gas_used) AS gas_used,
+    SUM(actual_gas_cost) AS native_cost_wei,
+    SUM(usd_amount) AS usd_amount
+FROM pm_user_operations
+WHERE UPPER(status) IN ('SUCCESS', 'FAILED')
+GROUP BY bucket, chain_id, paymaster_mode, paymaster_id, org_id, policy_id
+WITH NO DATA;
+
+-- Daily spend, rolled up from the hourly aggregate
+CREATE MATERIALIZED VIEW IF NOT EXISTS user_op_spend_daily
+WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+SELECT
+    time_bucket(INTERVAL '1 day', bucket) AS bucket,
+    chain_id,
+    paymaster_mode,
+    paymaster_id,
+    org_id,
+    policy_id,
+    SUM(ops)::BIGINT AS ops,
+    SUM(failed_ops)::BIGINT AS failed_ops,
+    SUM(gas_used) AS gas_used,
+    SUM(native_cost_wei) AS native_cost_wei,
+    SUM(usd_amount) AS usd_amount
+FROM user_op_spend_hourly
+GROUP BY time_bucket(INTERVAL '1 day', bucket), chain_id, paymaster_mode, paymaster_id, org_id, policy_id
+WITH NO DATA;
+
+-- Ops change status for a while after they land (receipts, reorgs), recent buckets are refreshed repeatedly
+SELECT add_continuous_aggregate_policy('user_op_spend_hourly',
+    start_offset => INTERVAL '3 days',
+    end_offset => INTERVAL '1 hour',
+    schedule_interval => INTERVAL '30 minutes',
+    if_not_exists => TRUE);
+
+SELECT add_continuous_aggregate_policy('user_op_spend_daily',
+    start_offset => INTERVAL '35 days',
+    end_offset => INTERVAL '1 day',
+    schedule_interval => INTERVAL '1 hour',
+    if_not_exists => TRUE);
+
+CREATE INDEX IF NOT EXISTS idx_user_op_spend_hourly_org
+  ON user_op_spend_hourly(org_id, bucket);
+
+CREATE INDEX IF NOT EXISTS idx_user_op_spend_daily_org
+  ON user_op_spend_daily(org_id, bucket);
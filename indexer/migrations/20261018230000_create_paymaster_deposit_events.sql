@@ -0,0 +1,37 @@
+-- Deposit and stake changes of our paymasters. paymaster_deposits is recomputed from them, so logs may be
+-- applied in any order (parallel workers, backfills) and a reorg rollback only has to delete the orphaned ones.
+CREATE TABLE IF NOT EXISTS paymaster_deposit_events (
+    chain_id INTEGER NOT NULL,
+    entry_point CHAR(42) NOT NULL,
+    paymaster CHAR(42) NOT NULL,
+    block_number BIGINT NOT NULL,
+    log_index BIGINT NOT NULL,               -- BIGINT max for RECONCILED, a balanceOf covers every log of its block
+    change VARCHAR(20) NOT NULL,             -- DEPOSITED, WITHDRAWN, GAS_CHARGED, STAKE_LOCKED, STAKE_UNLOCKED, STAKE_WITHDRAWN, RECONCILED
+    amount NUMERIC,                          -- In wei: total deposit (DEPOSITED, RECONCILED), amount taken or total stake
+    seconds BIGINT,                          -- Unstake delay (STAKE_LOCKED) or withdraw time (STAKE_UNLOCKED)
+    PRIMARY KEY (chain_id, entry_point, paymaster, block_number, log_index, change)
+);
+
+CREATE INDEX IF NOT EXISTS idx_paymaster_deposit_events_block ON paymaster_deposit_events (chain_id, block_number);
+
+-- Seed the current running values as the starting point of every existing row
+INSERT INTO paymaster_deposit_events (chain_id, entry_point, paymaster, block_number, log_index, change, amount)
+SELECT chain_id, entry_point, paymaster, last_block, last_log_index, 'RECONCILED', deposit
+FROM paymaster_deposits
+WHERE deposit IS NOT NULL
+ON CONFLICT DO NOTHING;
+
+INSERT INTO paymaster_deposit_events (chain_id, entry_point, paymaster, block_number, log_index, change, amount, seconds)
+SELECT chain_id, entry_point, paymaster, last_block, last_log_index,
+       CASE WHEN stake = 0 THEN 'STAKE_WITHDRAWN' ELSE 'STAKE_LOCKED' END,
+       stake, unstake_delay_sec
+FROM paymaster_deposits
+WHERE stake IS NOT NULL
+ON CONFLICT DO NOTHING;
+
+-- Same position as the lock, STAKE_UNLOCKED sorts after STAKE_LOCKED on ties
+INSERT INTO paymaster_deposit_events (chain_id, entry_point, paymaster, block_number, log_index, change, seconds)
+SELECT chain_id, entry_point, paymaster, last_block, last_log_index, 'STAKE_UNLOCKED', withdraw_time
+FROM paymaster_deposits
+WHERE stake > 0 AND withdraw_time > 0
+ON CONFLICT DO NOTHING;
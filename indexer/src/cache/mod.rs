@@ -9,4 +9,13 @@ pub trait Cache {
     async fn get_last_synced_block(&self, chain_id : u32) -> Result<Option<u64>, RedisError>;
     async fn set_last_synced_block(&self, chain_id : u32, block_number: u64) -> Result<(), Error>;
     async fn update_userop_policy(&self, user_op_hash: &str, partial: UserOpPolicyData) -> Result<(), Error>;
+    /// Reverts the usage counters applied for a user op, used when its block is reorged out
+    async fn revert_userop_usage(&self, user_op_hash: &str) -> Result<(), Error>;
+    /// Indexed block hashes for a chain, sorted by block number
+    async fn get_block_hashes(&self, chain_id: u32) -> Result<Vec<(u64, String)>, Error>;
+    /// Remembers indexed block hashes and forgets the ones below `keep_from`
+    async fn record_block_hashes(&self, chain_id: u32, hashes: &[(u64, String)], keep_from: u64) -> Result<(), Error>;
+    /// Drops the cached sync block, so the next read goes to the storage checkpoint
+    async fn discard_last_synced_block(&self, chain_id: u32) -> Result<(), Error>;
+    async fn discard_block_hashes_from(&self, chain_id: u32, from_block: u64) -> Result<(), Error>;
 }
@@ -1,10 +1,18 @@
+use std::collections::HashMap;
+
 use redis::{AsyncCommands, RedisError};
 use serde_json;
-use crate::model::user_op_policy::UserOpPolicyData;
+use crate::model::user_op_policy::{AppliedUsage, UserOpPolicyData};
 use crate::cache::Cache;
 use anyhow::Error;
 use async_trait::async_trait;
-use crate::utils::{calculate_usd_spent, parse_gas_value, append_usage_update_cmds};
+use crate::utils::{calculate_usd_spent, parse_wei, u256_to_decimal, wei_to_native, append_usage_update_cmds, append_usage_revert_cmds};
+use anyhow::Context;
+use sqlx::types::BigDecimal;
+use std::str::FromStr;
+
+// Applied usage is only needed while the block can still be reorged out
+const APPLIED_USAGE_TTL_SECS: u64 = 86400;
 
 pub struct RedisCoordinator {
     redis: redis::Client,
@@ -63,7 +71,7 @@ impl Cache for RedisCoordinator {
 
         if is_complete {
             tracing::info!("✅ Complete info for {}. Proceeding to update counters.", user_op_hash);
-            Self::update_usage_limits(&mut conn, &merged).await?;
+            Self::update_usage_limits(&mut conn, user_op_hash, &merged).await?;
             let _: () = conn.del(&key).await?;
         } else {
             let serialized = serde_json::to_string(&merged)?;
@@ -93,13 +101,90 @@ impl Cache for RedisCoordinator {
         conn.set::<_, _, ()>(key, block_number).await.map_err(Error::from)?;
         Ok(())
     }
+
+    async fn discard_last_synced_block(&self, chain_id: u32) -> Result<(), Error> {
+        let mut conn = self.redis.get_async_connection().await?;
+        let key = format!("sync_block:{}", chain_id);
+        conn.del::<_, ()>(key).await.map_err(Error::from)?;
+        Ok(())
+    }
+
+    async fn revert_userop_usage(&self, user_op_hash: &str) -> Result<(), Error> {
+        let mut conn = self.redis.get_async_connection().await?;
+        let key = format!("userop:applied:{}", user_op_hash);
+
+        let Some(json_str) = conn.get::<_, Option<String>>(&key).await? else {
+            // Nothing was counted yet, only drop the pending merge state
+            let _: () = conn.del(format!("userop:pending:{}", user_op_hash)).await?;
+            return Ok(());
+        };
+        let applied: AppliedUsage = serde_json::from_str(&json_str)?;
+        let usd_spent = BigDecimal::from_str(&applied.usd_spent).context("Invalid applied USD amount")?;
+        let gas_cost_eth = BigDecimal::from_str(&applied.gas_cost_eth).context("Invalid applied gas cost")?;
+
+        let mut pipe = redis::pipe();
+        if applied.enabled_limits.contains(&"GLOBAL".to_string()) {
+            append_usage_revert_cmds(&mut pipe, "global", &applied.policy_id, None, applied.gas, &usd_spent, &gas_cost_eth);
+        }
+        if applied.enabled_limits.contains(&"USER".to_string()) {
+            if let Some(user) = applied.sender.as_ref() {
+                append_usage_revert_cmds(&mut pipe, "user", &applied.policy_id, Some(user), applied.gas, &usd_spent, &gas_cost_eth);
+            }
+        }
+        pipe.cmd("DEL").arg(&key);
+
+        let _: () = pipe.query_async(&mut conn).await?;
+        tracing::info!("↩️ Reverted usage for {}: ops-=1 gas-={} usd-={}", user_op_hash, applied.gas, usd_spent);
+        Ok(())
+    }
+
+    async fn get_block_hashes(&self, chain_id: u32) -> Result<Vec<(u64, String)>, Error> {
+        let mut conn = self.redis.get_async_connection().await?;
+        let key = format!("block_hashes:{}", chain_id);
+        let stored: HashMap<u64, String> = conn.hgetall(key).await?;
+        let mut hashes: Vec<(u64, String)> = stored.into_iter().collect();
+        hashes.sort_by_key(|(block_number, _)| *block_number);
+        Ok(hashes)
+    }
+
+    async fn record_block_hashes(
+        &self,
+        chain_id: u32,
+        hashes: &[(u64, String)],
+        keep_from: u64,
+    ) -> Result<(), Error> {
+        let mut conn = self.redis.get_async_connection().await?;
+        let key = format!("block_hashes:{}", chain_id);
+        if !hashes.is_empty() {
+            let _: () = conn.hset_multiple(&key, hashes).await?;
+        }
+
+        let stored: Vec<u64> = conn.hkeys(&key).await?;
+        let expired: Vec<u64> = stored.into_iter().filter(|b| *b < keep_from).collect();
+        if !expired.is_empty() {
+            let _: () = conn.hdel(&key, expired).await?;
+        }
+        Ok(())
+    }
+
+    async fn discard_block_hashes_from(&self, chain_id: u32, from_block: u64) -> Result<(), Error> {
+        let mut conn = self.redis.get_async_connection().await?;
+        let key = format!("block_hashes:{}", chain_id);
+        let stored: Vec<u64> = conn.hkeys(&key).await?;
+        let discarded: Vec<u64> = stored.into_iter().filter(|b| *b >= from_block).collect();
+        if !discarded.is_empty() {
+            let _: () = conn.hdel(&key, discarded).await?;
+        }
+        Ok(())
+    }
 }
 
 impl RedisCoordinator {
     async fn update_usage_limits(
         conn: &mut redis::aio::Connection,
+        user_op_hash: &str,
         data: &UserOpPolicyData,
-    ) -> redis::RedisResult<()> {
+    ) -> Result<(), Error> {
         let Some(enabled) = data.enabled_limits.as_ref() else {
             tracing::info!("⚠️ Skipping update: no enabled limits specified.");
             return Ok(());
@@ -113,25 +198,41 @@ impl RedisCoordinator {
         let usd_price_str = data.native_usd_price.as_ref().unwrap();
         let actual_gas_cost_str = data.actual_gas_cost.as_ref().unwrap();
 
-        let usd_spent = calculate_usd_spent(actual_gas_cost_str, usd_price_str).unwrap_or(0.0);
-        let gas = parse_gas_value(data.actual_gas_used.as_ref());
-        let gas_cost_wei = parse_gas_value(data.actual_gas_cost.as_ref());
-        let gas_cost_eth = gas_cost_wei as f64 / 1e18;
+        // Invalid amounts fail the update instead of being counted as zero
+        let usd_spent = calculate_usd_spent(actual_gas_cost_str, usd_price_str)
+            .with_context(|| format!("Invalid gas cost {:?} or USD price {:?}", actual_gas_cost_str, usd_price_str))?;
+        let gas_used = data.actual_gas_used.as_deref().and_then(parse_wei).unwrap_or_default();
+        let gas = u64::try_from(gas_used).with_context(|| format!("Gas used {} does not fit a Redis counter", gas_used))?;
+        let gas_cost_eth = wei_to_native(&u256_to_decimal(parse_wei(actual_gas_cost_str).unwrap_or_default()));
 
         let mut pipe = redis::pipe();
         if enabled.contains(&"GLOBAL".to_string()) {
             tracing::info!("🔄 Updating global usage limits");
-            append_usage_update_cmds(&mut pipe, "global", policy_id, None, gas, usd_spent, gas_cost_eth);
+            append_usage_update_cmds(&mut pipe, "global", policy_id, None, gas, &usd_spent, &gas_cost_eth);
         }
         if enabled.contains(&"USER".to_string()) {
             tracing::info!("🔄 Updating user-specific usage limits");
             if let Some(user) = data.sender.as_ref() {
-                append_usage_update_cmds(&mut pipe, "user", policy_id, Some(user), gas, usd_spent, gas_cost_eth);
+                append_usage_update_cmds(&mut pipe, "user", policy_id, Some(user), gas, &usd_spent, &gas_cost_eth);
             }
         }
 
+        // Remember what was counted so a reorg can take it back
+        let applied = AppliedUsage {
+            policy_id: policy_id.clone(),
+            sender: data.sender.clone(),
+            enabled_limits: enabled.clone(),
+            gas,
+            usd_spent: usd_spent.to_string(),
+            gas_cost_eth: gas_cost_eth.to_string(),
+        };
+        if let Ok(serialized) = serde_json::to_string(&applied) {
+            pipe.cmd("SET").arg(format!("userop:applied:{}", user_op_hash)).arg(serialized)
+                .arg("EX").arg(APPLIED_USAGE_TTL_SECS);
+        }
+
         let _: () = pipe.query_async(conn).await?;
-        tracing::info!("🔄 Updated usage (scopes: {:?}): ops+=1 gas+={} usd+={:.4}", enabled, gas, usd_spent);
+        tracing::info!("🔄 Updated usage (scopes: {:?}): ops+=1 gas+={} usd+={}", enabled, gas, usd_spent);
         Ok(())
     }
 }
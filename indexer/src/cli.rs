@@ -0,0 +1,104 @@
+use indexer::listener::backfill::BackfillJob;
+use indexer::model::dead_letter::DeadLetterSource;
+
+pub const USAGE: &str = "Usage:
+  indexer                      Run live indexing and the Kafka consumer
+  indexer backfill --chain <name> [--from <block>] [--to <block>] [--contract <name>]... [--job <name>]
+                               Index a historical range next to live indexing
+  indexer replay [--source <log|kafka>] [--limit <n>]
+                               Run unresolved dead letters through the current handlers";
+
+/// **What the binary was asked to do**
+pub enum Command {
+    Run,
+    Backfill { chain: String, job: BackfillJob },
+    Replay { source: Option<DeadLetterSource>, limit: i64 },
+}
+
+impl Command {
+    /// **Parse command line arguments, without the program name**
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        match args.next().as_deref() {
+            None | Some("run") => Ok(Command::Run),
+            Some("backfill") => parse_backfill(args),
+            Some("replay") => parse_replay(args),
+            Some(other) => Err(format!("unknown command: {}", other)),
+        }
+    }
+}
+
+fn parse_backfill(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut chain = None;
+    let mut job_name = None;
+    let mut contracts = Vec::new();
+    let mut from_block = None;
+    let mut to_block = None;
+
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", flag));
+        match flag.as_str() {
+            "--chain" => chain = Some(value()?),
+            "--job" => job_name = Some(value()?),
+            "--contract" => contracts.push(value()?),
+            "--from" => from_block = Some(parse_block(&value()?)?),
+            "--to" => to_block = Some(parse_block(&value()?)?),
+            _ => return Err(format!("unknown argument: {}", flag)),
+        }
+    }
+
+    let chain = chain.ok_or("--chain is required")?;
+    // Jobs with the same name share a cursor, so a rerun resumes where the last one stopped
+    let name = job_name.unwrap_or_else(|| {
+        if contracts.is_empty() {
+            "all".to_string()
+        } else {
+            contracts.join("+")
+        }
+    });
+
+    Ok(Command::Backfill {
+        chain,
+        job: BackfillJob {
+            name,
+            contracts,
+            from_block,
+            to_block,
+        },
+    })
+}
+
+fn parse_replay(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut source = None;
+    let mut limit = 1000;
+
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", flag));
+        match flag.as_str() {
+            "--source" => {
+                source = Some(match value()?.as_str() {
+                    "log" => DeadLetterSource::Log,
+                    "kafka" => DeadLetterSource::Kafka,
+                    other => return Err(format!("invalid source: {}", other)),
+                })
+            }
+            "--limit" => {
+                let raw = value()?;
+                limit = raw
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|limit| *limit > 0)
+                    .ok_or_else(|| format!("invalid limit: {}", raw))?;
+            }
+            _ => return Err(format!("unknown argument: {}", flag)),
+        }
+    }
+
+    Ok(Command::Replay { source, limit })
+}
+
+fn parse_block(value: &str) -> Result<u64, String> {
+    value
+        .parse::<u64>()
+        .map_err(|_| format!("invalid block number: {}", value))
+}
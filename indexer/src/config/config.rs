@@ -2,6 +2,8 @@ use serde::Deserialize;
 use std::{collections::HashMap, fs, env};
 use dotenv::dotenv;
 
+use crate::events::entry_point::EntryPointVersion;
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub general: GeneralConfig,
@@ -13,31 +15,104 @@ pub struct Config {
 pub struct ContractConfig {
     pub name: String,
     pub address: String,
+    pub start_block: Option<u64>, // ✅ Deployment block, where backfills start by default
+    pub entry_point_version: Option<EntryPointVersion>, // ✅ Set on EntryPoint contracts ("0.6", "0.7" or "0.8")
+    pub min_deposit: Option<String>, // ✅ Paymasters only: warn when the EntryPoint deposit drops below this, in native token (e.g. "0.5")
+    #[serde(default)]
+    pub entry_points: Vec<String>, // ✅ Paymasters only: EntryPoints holding its deposit, checked when min_deposit is set
     pub events: Vec<EventConfig>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GeneralConfig {
     pub indexer_name: String,
+    pub metrics_port: Option<u16>, // ✅ Serve Prometheus metrics on this port when set
+    #[serde(default = "default_dead_letter_max_attempts")]
+    pub dead_letter_max_attempts: i32, // ✅ Failures of the same log before it is dead-lettered and skipped
+    #[serde(default = "default_aggregate_refresh_interval_secs")]
+    pub aggregate_refresh_interval_secs: u64, // ✅ How often the spend aggregates catch up on changed history
 }
 
 #[derive(Debug,Clone, Deserialize)]
 pub struct ChainConfig {
     pub active: bool,
     pub rpc_url: String,
+    #[serde(default)]
+    pub rpc_urls: Vec<String>, // ✅ Extra endpoints, tried in order of health
+    pub ws_url: Option<String>, // ✅ Subscribe to newHeads instead of waiting for the poll interval
+    #[serde(default = "default_rpc_timeout_secs")]
+    pub rpc_timeout_secs: u64,
     pub chain_id: u32,
     pub block_time: u64,
     pub polling_blocks: u64,
     pub reorg_buffer: u64,
+    #[serde(default = "default_reorg_window")]
+    pub reorg_window: u64,   // ✅ How many recent blocks are checked for reorgs
+    #[serde(default = "default_max_block_range")]
+    pub max_block_range: u64, // ✅ Largest block range requested per eth_getLogs call
+    #[serde(default = "default_deposit_check_interval_secs")]
+    pub deposit_check_interval_secs: u64, // ✅ How often paymaster deposits are checked against balanceOf
+    #[serde(default = "default_processor_workers")]
+    pub processor_workers: usize, // ✅ Workers processing this chain's events, partitioned by transaction
+    #[serde(default = "default_processor_queue_size")]
+    pub processor_queue_size: usize, // ✅ Events buffered per queue before the listener waits
     pub use_finalized: bool,
     pub contracts: Vec<ContractConfig>,
 }
 
+impl ChainConfig {
+    /// All configured RPC endpoints, `rpc_url` first
+    pub fn rpc_endpoints(&self) -> Vec<String> {
+        let mut endpoints: Vec<String> = Vec::new();
+        for url in std::iter::once(&self.rpc_url).chain(self.rpc_urls.iter()) {
+            let url = url.trim();
+            if !url.is_empty() && !endpoints.iter().any(|e| e == url) {
+                endpoints.push(url.to_string());
+            }
+        }
+        endpoints
+    }
+}
+
+fn default_dead_letter_max_attempts() -> i32 {
+    5
+}
+
+fn default_aggregate_refresh_interval_secs() -> u64 {
+    3600
+}
+
+fn default_rpc_timeout_secs() -> u64 {
+    30
+}
+
+fn default_reorg_window() -> u64 {
+    64
+}
+
+fn default_max_block_range() -> u64 {
+    2000
+}
+
+fn default_deposit_check_interval_secs() -> u64 {
+    300
+}
+
+fn default_processor_workers() -> usize {
+    1
+}
+
+fn default_processor_queue_size() -> usize {
+    100
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct EventConfig {
     pub signature: String,    // ✅ Event signature hash (e.g., "0x4962...")
     pub name: String,         // ✅ Event name (e.g., "UserOperationEvent")
     pub params: Vec<String>,  // ✅ Event parameter types (e.g., ["bytes32", "address", ...])
+    #[serde(default)]
+    pub abi: Option<String>,  // ✅ Full event ABI, logs are decoded from it and stored in contract_events
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,6 +122,18 @@ pub struct StorageConfig {
     pub kafka_group_id: String,
     pub timescale_db_url: String,
     pub redis_url: String,
+    #[serde(default = "default_user_op_batch_window_ms")]
+    pub user_op_batch_window_ms: u64, // ✅ Longest wait for writers still sending, a drained queue is written right away
+    #[serde(default = "default_user_op_batch_size")]
+    pub user_op_batch_size: usize, // ✅ Largest user op batch, written early once full
+}
+
+fn default_user_op_batch_window_ms() -> u64 {
+    20
+}
+
+fn default_user_op_batch_size() -> usize {
+    500
 }
 
 impl Config {
@@ -69,6 +156,17 @@ impl Config {
             if let Ok(rpc_url) = env::var(&env_var_name) {
                 chain_config.rpc_url = rpc_url;
             }
+            let env_var_name: String = format!("{}_RPC_URLS", chain_name.to_uppercase());
+            if let Ok(rpc_urls) = env::var(&env_var_name) {
+                chain_config.rpc_urls = rpc_urls.split(',').map(String::from).collect();
+            }
+            let env_var_name: String = format!("{}_WS_URL", chain_name.to_uppercase());
+            if let Ok(ws_url) = env::var(&env_var_name) {
+                chain_config.ws_url = Some(ws_url);
+            }
+
+            // 🔹 **A zero block range would never fetch anything**
+            chain_config.max_block_range = chain_config.max_block_range.max(1);
         }
 
         // 🔹 **Override Storage Configuration Dynamically ENV**
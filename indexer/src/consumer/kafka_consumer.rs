@@ -3,6 +3,8 @@ use std::sync::Arc;
 use rdkafka::consumer::{Consumer, StreamConsumer};
 use rdkafka::ClientConfig;
 use rdkafka::message::Message;
+use anyhow::Context;
+use crate::model::dead_letter::{DeadLetter, DeadLetterSource, DeadLetterStage, UnprocessableInput};
 use crate::model::paymaster_type::PaymasterMode;
 use crate::{
     model::{user_op_policy::UserOpPolicyData, user_op::UserOpMessage},
@@ -37,32 +39,9 @@ where
             Ok(m) => {
                 if let Some(payload) = m.payload_view::<str>().and_then(Result::ok) {
                     tracing::info!("📥 Received message: {:?}", payload);
-                    match serde_json::from_str::<UserOpMessage>(payload) {
-                        Ok(event) => {
-                            if matches!(
-                                event.paymaster_mode,
-                                Some(PaymasterMode::SponsorshipPrepaid | PaymasterMode::SponsorshipPostpaid)
-                            ) {
-                                if let Some(policy_id) = event.policy_id.clone() {
-                                    tracing::info!("🟢 Updating Redis with policy_id: {}", policy_id);
-                                    let redis_payload = UserOpPolicyData {
-                                        policy_id: Some(policy_id),
-                                        native_usd_price: event.native_usd_price.clone(),
-                                        sender: event.user_op.get("sender").and_then(|v| v.as_str()).map(|s| s.to_string()),
-                                        enabled_limits: event.enabled_limits.clone(),
-                                        actual_gas_used: None,
-                                        actual_gas_cost: None,
-                                    };
-                                    if let Err(e) = app.cache.update_userop_policy(&event.user_op_hash, redis_payload).await {
-                                        tracing::error!("❌ Failed to update Redis policy: {:?}", e);
-                                    }
-                                }
-                            }
-                            if let Err(e) = app.storage.upsert_user_op_message(event).await {
-                                tracing::error!("❌ Failed to upsert UserOpMessage into Timescale: {:?}", e);
-                            }
-                        }
-                        Err(e) => tracing::error!("❌ Failed to deserialize UserOpMessage: {:?}", e),
+                    if let Err(e) = handle_message(payload, &app).await {
+                        let entry_key = format!("{}:{}:{}", m.topic(), m.partition(), m.offset());
+                        dead_letter(&app, entry_key, payload, e).await;
                     }
                 } else {
                     tracing::error!("❌ Failed to get payload from message");
@@ -72,3 +51,64 @@ where
         }
     }
 }
+
+/// **Store one UserOpMessage payload, used by the consumer and by `indexer replay`**
+pub async fn handle_message<S, C>(payload: &str, app: &Arc<AppContext<S, C>>) -> anyhow::Result<()>
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    let event = serde_json::from_str::<UserOpMessage>(payload)
+        .map_err(|e| UnprocessableInput(format!("Failed to deserialize UserOpMessage: {}", e)))?;
+
+    if matches!(
+        event.paymaster_mode,
+        Some(PaymasterMode::SponsorshipPrepaid | PaymasterMode::SponsorshipPostpaid)
+    ) {
+        if let Some(policy_id) = event.policy_id.clone() {
+            tracing::info!("🟢 Updating Redis with policy_id: {}", policy_id);
+            let redis_payload = UserOpPolicyData {
+                policy_id: Some(policy_id),
+                native_usd_price: event.native_usd_price.clone(),
+                sender: event.user_op.get("sender").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                enabled_limits: event.enabled_limits.clone(),
+                actual_gas_used: None,
+                actual_gas_cost: None,
+            };
+            if let Err(e) = app.cache.update_userop_policy(&event.user_op_hash, redis_payload).await {
+                tracing::error!("❌ Failed to update Redis policy: {:?}", e);
+            }
+        }
+    }
+    app.storage
+        .upsert_user_op_message(event)
+        .await
+        .context("Failed to upsert UserOpMessage into Timescale")
+}
+
+/// **Keep a payload that could not be stored, the consumer moves on either way**
+async fn dead_letter<S, C>(app: &Arc<AppContext<S, C>>, entry_key: String, payload: &str, error: anyhow::Error)
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    tracing::error!("❌ {:?}", error);
+    let stage = if error.downcast_ref::<UnprocessableInput>().is_some() {
+        DeadLetterStage::Parse
+    } else {
+        DeadLetterStage::Store
+    };
+    let letter = DeadLetter {
+        source: DeadLetterSource::Kafka,
+        entry_key,
+        chain_id: None,
+        block_number: None,
+        log_index: None,
+        stage,
+        error: format!("{:?}", error),
+        payload: payload.to_string(),
+    };
+    if let Err(e) = app.storage.record_dead_letter(letter).await {
+        tracing::error!("❌ Failed to record dead letter: {:?}", e);
+    }
+}
@@ -0,0 +1,57 @@
+use alloy::dyn_abi::{DynSolValue, EventExt};
+use alloy::json_abi::Event as AbiEvent;
+use alloy::primitives::{LogData, B256};
+use anyhow::Context;
+use serde_json::{json, Map, Value};
+
+/// **Parse a full event ABI, e.g. `event Transfer(address indexed from, address indexed to, uint256 value)`**
+///
+/// The ABI must hash to `signature`, so a typo is caught at startup rather than skipping every log.
+pub fn parse_event_abi(abi: &str, signature: B256) -> anyhow::Result<AbiEvent> {
+    let event = AbiEvent::parse(abi).with_context(|| format!("invalid event ABI: {}", abi))?;
+    anyhow::ensure!(!event.anonymous, "anonymous events have no signature topic: {}", abi);
+    anyhow::ensure!(
+        event.selector() == signature,
+        "ABI {} hashes to {:?}, not the configured signature {:?}",
+        abi,
+        event.selector(),
+        signature
+    );
+    Ok(event)
+}
+
+/// **Decode a log into a JSON object of its parameters, by name and in declaration order**
+///
+/// Unnamed parameters are keyed by position. Indexed dynamic values (string, bytes, arrays)
+/// only exist as their keccak256 hash in the topics and are returned as such.
+pub fn decode_event_args(event: &AbiEvent, log: &LogData) -> anyhow::Result<Value> {
+    let decoded = event.decode_log(log, true).with_context(|| format!("failed to decode {} log", event.name))?;
+    let mut indexed = decoded.indexed.into_iter();
+    let mut body = decoded.body.into_iter();
+
+    let mut args = Map::new();
+    for (position, input) in event.inputs.iter().enumerate() {
+        let value = if input.indexed { indexed.next() } else { body.next() };
+        let value = value.with_context(|| format!("{} log is missing parameter {}", event.name, position))?;
+        let key = if input.name.is_empty() { position.to_string() } else { input.name.clone() };
+        args.insert(key, value_to_json(value));
+    }
+    Ok(Value::Object(args))
+}
+
+// Integers are strings so uint256 values survive JSONB, bytes are 0x hex
+fn value_to_json(value: DynSolValue) -> Value {
+    match value {
+        DynSolValue::Bool(b) => json!(b),
+        DynSolValue::Int(i, _) => json!(i.to_string()),
+        DynSolValue::Uint(u, _) => json!(u.to_string()),
+        DynSolValue::FixedBytes(word, size) => json!(format!("0x{}", alloy::hex::encode(&word[..size]))),
+        DynSolValue::Address(address) => json!(format!("{:?}", address)),
+        DynSolValue::Function(function) => json!(function.to_string()),
+        DynSolValue::Bytes(bytes) => json!(format!("0x{}", alloy::hex::encode(bytes))),
+        DynSolValue::String(s) => json!(s),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) | DynSolValue::Tuple(values) => {
+            Value::Array(values.into_iter().map(value_to_json).collect())
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt;
+
+use alloy::primitives::{keccak256, Address, B256, U256};
+use alloy::sol;
+use alloy_sol_types::{SolCall, SolValue};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// **EntryPoint release a configured contract is deployed from**
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum EntryPointVersion {
+    #[serde(rename = "0.6")]
+    V06,
+    #[serde(rename = "0.7")]
+    V07,
+    #[serde(rename = "0.8")]
+    V08,
+}
+
+impl fmt::Display for EntryPointVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = match self {
+            EntryPointVersion::V06 => "0.6",
+            EntryPointVersion::V07 => "0.7",
+            EntryPointVersion::V08 => "0.8",
+        };
+        write!(f, "{}", version)
+    }
+}
+
+// ✅ EntryPoint v0.6 bundle entry points, ops are not packed yet
+pub mod v06 {
+    use alloy::sol;
+
+    sol! {
+        #[derive(Debug)]
+        struct UserOperation {
+            address sender;
+            uint256 nonce;
+            bytes initCode;
+            bytes callData;
+            uint256 callGasLimit;
+            uint256 verificationGasLimit;
+            uint256 preVerificationGas;
+            uint256 maxFeePerGas;
+            uint256 maxPriorityFeePerGas;
+            bytes paymasterAndData;
+            bytes signature;
+        }
+
+        #[derive(Debug)]
+        struct UserOpsPerAggregator {
+            UserOperation[] userOps;
+            address aggregator;
+            bytes signature;
+        }
+
+        #[derive(Debug)]
+        function handleOps(UserOperation[] ops, address beneficiary);
+
+        #[derive(Debug)]
+        function handleAggregatedOps(UserOpsPerAggregator[] opsPerAggregator, address beneficiary);
+    }
+}
+
+// ✅ EntryPoint v0.7 / v0.8 bundle entry points, used to decode handleOps calldata
+sol! {
+    #[derive(Debug)]
+    struct PackedUserOperation {
+        address sender;
+        uint256 nonce;
+        bytes initCode;
+        bytes callData;
+        bytes32 accountGasLimits;
+        uint256 preVerificationGas;
+        bytes32 gasFees;
+        bytes paymasterAndData;
+        bytes signature;
+    }
+
+    #[derive(Debug)]
+    struct UserOpsPerAggregator {
+        PackedUserOperation[] userOps;
+        address aggregator;
+        bytes signature;
+    }
+
+    #[derive(Debug)]
+    function handleOps(PackedUserOperation[] ops, address beneficiary);
+
+    #[derive(Debug)]
+    function handleAggregatedOps(UserOpsPerAggregator[] opsPerAggregator, address beneficiary);
+}
+
+// ✅ EntryPoint views, the same in every version
+sol! {
+    function balanceOf(address account) external view returns (uint256);
+}
+
+// EIP-712 type hashes used by EntryPoint v0.8 getUserOpHash
+const PACKED_USER_OP_TYPE: &str = "PackedUserOperation(address sender,uint256 nonce,bytes initCode,bytes callData,bytes32 accountGasLimits,uint256 preVerificationGas,bytes32 gasFees,bytes paymasterAndData)";
+const EIP712_DOMAIN_TYPE: &str = "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+
+/// **A user op as found in bundle calldata**
+#[derive(Debug)]
+pub enum BundledUserOp {
+    V06(v06::UserOperation),
+    Packed(PackedUserOperation), // v0.7 and v0.8 share the calldata layout
+}
+
+/// **A decoded handleOps / handleAggregatedOps bundle**
+#[derive(Debug)]
+pub struct Bundle {
+    pub beneficiary: Address,
+    pub user_ops: Vec<BundledUserOp>,
+}
+
+/// **Decode bundle calldata of any supported version, `None` when the transaction is not a handleOps call**
+pub fn decode_bundle(input: &[u8]) -> Option<Bundle> {
+    if let Ok(call) = handleOpsCall::abi_decode(input, true) {
+        return Some(Bundle {
+            beneficiary: call.beneficiary,
+            user_ops: call.ops.into_iter().map(BundledUserOp::Packed).collect(),
+        });
+    }
+    if let Ok(call) = handleAggregatedOpsCall::abi_decode(input, true) {
+        return Some(Bundle {
+            beneficiary: call.beneficiary,
+            user_ops: call
+                .opsPerAggregator
+                .into_iter()
+                .flat_map(|per_aggregator| per_aggregator.userOps)
+                .map(BundledUserOp::Packed)
+                .collect(),
+        });
+    }
+    if let Ok(call) = v06::handleOpsCall::abi_decode(input, true) {
+        return Some(Bundle {
+            beneficiary: call.beneficiary,
+            user_ops: call.ops.into_iter().map(BundledUserOp::V06).collect(),
+        });
+    }
+    if let Ok(call) = v06::handleAggregatedOpsCall::abi_decode(input, true) {
+        return Some(Bundle {
+            beneficiary: call.beneficiary,
+            user_ops: call
+                .opsPerAggregator
+                .into_iter()
+                .flat_map(|per_aggregator| per_aggregator.userOps)
+                .map(BundledUserOp::V06)
+                .collect(),
+        });
+    }
+    None
+}
+
+impl Bundle {
+    /// **Find the op an UserOperationEvent was emitted for**
+    pub fn find_user_op(
+        &self,
+        user_op_hash: B256,
+        version: EntryPointVersion,
+        entry_point: Address,
+        chain_id: u64,
+    ) -> Option<&BundledUserOp> {
+        self.user_ops
+            .iter()
+            .find(|op| op.user_op_hash(version, entry_point, chain_id) == Some(user_op_hash))
+    }
+}
+
+impl BundledUserOp {
+    /// **userOpHash as computed by EntryPoint.getUserOpHash, `None` if the op does not fit the version**
+    ///
+    /// v0.8 ops using an EIP-7702 initCode hash the account's delegate, which is not in
+    /// the calldata, so they never match and the caller falls back to the event fields.
+    pub fn user_op_hash(&self, version: EntryPointVersion, entry_point: Address, chain_id: u64) -> Option<B256> {
+        match (self, version) {
+            (BundledUserOp::V06(op), EntryPointVersion::V06) => {
+                let packed = (
+                    op.sender,
+                    op.nonce,
+                    keccak256(&op.initCode),
+                    keccak256(&op.callData),
+                    op.callGasLimit,
+                    op.verificationGasLimit,
+                    op.preVerificationGas,
+                    op.maxFeePerGas,
+                    op.maxPriorityFeePerGas,
+                    keccak256(&op.paymasterAndData),
+                )
+                    .abi_encode();
+                Some(keccak256((keccak256(packed), entry_point, U256::from(chain_id)).abi_encode()))
+            }
+            (BundledUserOp::Packed(op), EntryPointVersion::V07) => {
+                Some(keccak256((keccak256(op.packed_fields()), entry_point, U256::from(chain_id)).abi_encode()))
+            }
+            (BundledUserOp::Packed(op), EntryPointVersion::V08) => {
+                let struct_hash = keccak256([keccak256(PACKED_USER_OP_TYPE).as_slice(), &op.packed_fields()].concat());
+                let domain_separator = keccak256(
+                    (
+                        keccak256(EIP712_DOMAIN_TYPE),
+                        keccak256("ERC4337"),
+                        keccak256("1"),
+                        U256::from(chain_id),
+                        entry_point,
+                    )
+                        .abi_encode(),
+                );
+                Some(keccak256([&[0x19, 0x01], domain_separator.as_slice(), struct_hash.as_slice()].concat()))
+            }
+            _ => None,
+        }
+    }
+
+    /// **Unpacked JSON form, as returned by the bundler RPC of the op's version**
+    pub fn to_json(&self) -> Value {
+        match self {
+            BundledUserOp::V06(op) => json!({
+                "sender": format!("{:?}", op.sender),
+                "nonce": format!("{:#x}", op.nonce),
+                "initCode": op.initCode.to_string(),
+                "callData": op.callData.to_string(),
+                "callGasLimit": format!("{:#x}", op.callGasLimit),
+                "verificationGasLimit": format!("{:#x}", op.verificationGasLimit),
+                "preVerificationGas": format!("{:#x}", op.preVerificationGas),
+                "maxFeePerGas": format!("{:#x}", op.maxFeePerGas),
+                "maxPriorityFeePerGas": format!("{:#x}", op.maxPriorityFeePerGas),
+                "paymasterAndData": op.paymasterAndData.to_string(),
+                "signature": op.signature.to_string(),
+            }),
+            BundledUserOp::Packed(op) => op.to_json(),
+        }
+    }
+
+}
+
+impl PackedUserOperation {
+    // Fields hashed by getUserOpHash, dynamic ones replaced by their keccak256
+    fn packed_fields(&self) -> Vec<u8> {
+        (
+            self.sender,
+            self.nonce,
+            keccak256(&self.initCode),
+            keccak256(&self.callData),
+            self.accountGasLimits,
+            self.preVerificationGas,
+            self.gasFees,
+            keccak256(&self.paymasterAndData),
+        )
+            .abi_encode()
+    }
+
+    /// **Unpacked JSON form, as returned by the bundler RPC**
+    pub fn to_json(&self) -> Value {
+        let (verification_gas_limit, call_gas_limit) = split_u128_pair(&self.accountGasLimits);
+        let (max_priority_fee_per_gas, max_fee_per_gas) = split_u128_pair(&self.gasFees);
+
+        let mut op = json!({
+            "sender": format!("{:?}", self.sender),
+            "nonce": format!("{:#x}", self.nonce),
+            "callData": self.callData.to_string(),
+            "callGasLimit": format!("{:#x}", call_gas_limit),
+            "verificationGasLimit": format!("{:#x}", verification_gas_limit),
+            "preVerificationGas": format!("{:#x}", self.preVerificationGas),
+            "maxFeePerGas": format!("{:#x}", max_fee_per_gas),
+            "maxPriorityFeePerGas": format!("{:#x}", max_priority_fee_per_gas),
+            "signature": self.signature.to_string(),
+        });
+
+        // initCode = factory (20 bytes) ++ factoryData
+        if self.initCode.len() >= 20 {
+            op["factory"] = json!(format!("{:?}", Address::from_slice(&self.initCode[..20])));
+            op["factoryData"] = json!(format!("0x{}", alloy::hex::encode(&self.initCode[20..])));
+        }
+
+        // paymasterAndData = paymaster (20) ++ verificationGasLimit (16) ++ postOpGasLimit (16) ++ paymasterData
+        if self.paymasterAndData.len() >= 52 {
+            let data = &self.paymasterAndData;
+            op["paymaster"] = json!(format!("{:?}", Address::from_slice(&data[..20])));
+            op["paymasterVerificationGasLimit"] = json!(format!("{:#x}", u128_from_be(&data[20..36])));
+            op["paymasterPostOpGasLimit"] = json!(format!("{:#x}", u128_from_be(&data[36..52])));
+            op["paymasterData"] = json!(format!("0x{}", alloy::hex::encode(&data[52..])));
+        }
+
+        op
+    }
+}
+
+// Two uint128 packed in a bytes32, high half first
+fn split_u128_pair(packed: &B256) -> (u128, u128) {
+    (u128_from_be(&packed[..16]), u128_from_be(&packed[16..]))
+}
+
+fn u128_from_be(bytes: &[u8]) -> u128 {
+    let mut buf = [0u8; 16];
+    buf.copy_from_slice(bytes);
+    u128::from_be_bytes(buf)
+}
@@ -24,4 +24,29 @@ sol! {
     // Events for Entry Point
     #[derive(Debug, Serialize)] 
     event UserOperationEvent(bytes32 indexed userOpHash, address indexed sender, address indexed paymaster, uint256 nonce, bool success, uint256 actualGasCost, uint256 actualGasUsed);
+
+    #[derive(Debug, Serialize)] 
+    event AccountDeployed(bytes32 indexed userOpHash, address indexed sender, address factory, address paymaster);
+
+    #[derive(Debug, Serialize)] 
+    event UserOperationRevertReason(bytes32 indexed userOpHash, address indexed sender, uint256 nonce, bytes revertReason);
+
+    #[derive(Debug, Serialize)] 
+    event PostOpRevertReason(bytes32 indexed userOpHash, address indexed sender, uint256 nonce, bytes revertReason);
+
+    // Deposit and stake events of the EntryPoint StakeManager
+    #[derive(Debug, Serialize)] 
+    event Deposited(address indexed account, uint256 totalDeposit);
+
+    #[derive(Debug, Serialize)] 
+    event Withdrawn(address indexed account, address withdrawAddress, uint256 amount);
+
+    #[derive(Debug, Serialize)] 
+    event StakeLocked(address indexed account, uint256 totalStaked, uint256 unstakeDelaySec);
+
+    #[derive(Debug, Serialize)] 
+    event StakeUnlocked(address indexed account, uint256 withdrawTime);
+
+    #[derive(Debug, Serialize)] 
+    event StakeWithdrawn(address indexed account, address withdrawAddress, uint256 amount);
 }
\ No newline at end of file
@@ -1 +1,5 @@
-pub mod events;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod events;
+pub mod entry_point;
+pub mod revert_reason;
+pub mod dynamic;
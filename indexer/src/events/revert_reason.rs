@@ -0,0 +1,78 @@
+use alloy::primitives::U256;
+use alloy_sol_types::{Panic, Revert, SolError};
+
+/// **Revert data of a failed user op or postOp, decoded when it uses a standard error**
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertReason {
+    /// `Error(string)`, from `require` and `revert("...")`
+    Error(String),
+    /// `Panic(uint256)`, from failed asserts, overflows and other compiler checks
+    Panic(U256),
+    /// Custom error, only the selector is known without the contract ABI
+    Custom { selector: [u8; 4] },
+    /// Empty or shorter than a selector
+    Unknown,
+}
+
+impl RevertReason {
+    pub fn decode(data: &[u8]) -> Self {
+        if let Ok(revert) = Revert::abi_decode(data, true) {
+            return RevertReason::Error(revert.reason);
+        }
+        if let Ok(panic) = Panic::abi_decode(data, true) {
+            return RevertReason::Panic(panic.code);
+        }
+        match data.get(..4) {
+            Some(selector) => RevertReason::Custom {
+                selector: selector.try_into().expect("4 byte slice"),
+            },
+            None => RevertReason::Unknown,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RevertReason::Error(_) => "Error",
+            RevertReason::Panic(_) => "Panic",
+            RevertReason::Custom { .. } => "Custom",
+            RevertReason::Unknown => "Unknown",
+        }
+    }
+
+    /// Human readable reason, `None` for custom errors
+    pub fn message(&self) -> Option<String> {
+        match self {
+            RevertReason::Error(reason) => Some(reason.clone()),
+            RevertReason::Panic(code) => Some(format!("Panic({:#x}): {}", code, panic_description(*code))),
+            RevertReason::Custom { .. } | RevertReason::Unknown => None,
+        }
+    }
+
+    /// 4-byte selector of the error, as 0x-prefixed hex
+    pub fn selector(&self) -> Option<String> {
+        let selector = match self {
+            RevertReason::Error(_) => Revert::SELECTOR,
+            RevertReason::Panic(_) => Panic::SELECTOR,
+            RevertReason::Custom { selector } => *selector,
+            RevertReason::Unknown => return None,
+        };
+        Some(format!("0x{}", alloy::hex::encode(selector)))
+    }
+}
+
+// Panic codes emitted by solc, see https://docs.soliditylang.org/en/latest/control-structures.html#panic-via-assert-and-error-via-require
+fn panic_description(code: U256) -> &'static str {
+    match u64::try_from(code).unwrap_or(u64::MAX) {
+        0x00 => "generic compiler panic",
+        0x01 => "assert failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array",
+        0x31 => "pop on empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to zero-initialized function",
+        _ => "unknown panic code",
+    }
+}
@@ -4,4 +4,14 @@ pub mod storage;
 pub mod app;
 pub mod cache;
 pub mod model;
-pub mod utils;
\ No newline at end of file
+pub mod utils;
+pub mod config;
+pub mod listener;
+pub mod metrics;
+pub mod processor;
+pub mod service;
+
+pub use app::AppContext;
+pub use model::event::Event;
+pub use processor::registry::EventHandler;
+pub use service::{Indexer, IndexerApp};
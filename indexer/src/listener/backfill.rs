@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use tokio::{sync::mpsc, time::sleep};
+
+use crate::{
+    cache::Cache,
+    config::config::{ChainConfig, ContractConfig},
+    listener::listener::{log_filter_targets, EventListener, SyncCursor},
+    model::event::Event,
+    storage::Storage,
+};
+
+const RETRY_DELAY_SECS: u64 = 5;
+
+/// **A historical range to index next to live indexing**
+#[derive(Debug, Clone)]
+pub struct BackfillJob {
+    pub name: String,
+    /// Contract names to backfill, all contracts of the chain when empty
+    pub contracts: Vec<String>,
+    /// Defaults to the lowest `start_block` of the selected contracts
+    pub from_block: Option<u64>,
+    /// Defaults to the live checkpoint, or the safe head when there is none
+    pub to_block: Option<u64>,
+}
+
+impl<S, C> EventListener<S, C>
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    /// **Index a historical block range, resuming from the job's own cursor**
+    pub async fn backfill(
+        &self,
+        chain_config: &ChainConfig,
+        job: &BackfillJob,
+        sender: mpsc::Sender<Event>,
+    ) -> anyhow::Result<()> {
+        let chain_id = chain_config.chain_id;
+        let contracts = select_contracts(chain_config, &job.contracts)?;
+        let (contract_addresses, event_signatures) = log_filter_targets(&contracts);
+
+        let safe_head = self
+            .safe_head(chain_config)
+            .await
+            .ok_or_else(|| anyhow!("failed to fetch the head of chain {}", chain_id))?;
+        let live_checkpoint = self.load_checkpoint(chain_id, &SyncCursor::Live).await?;
+        let to_block = job
+            .to_block
+            .or(live_checkpoint)
+            .unwrap_or(safe_head)
+            .min(safe_head);
+
+        let start_block = job
+            .from_block
+            .or_else(|| contracts.iter().filter_map(|c| c.start_block).min())
+            .ok_or_else(|| anyhow!("no from block given and no start_block configured for the selected contracts"))?;
+        let cursor = SyncCursor::Backfill(job.name.clone());
+        let from_block = match self.load_checkpoint(chain_id, &cursor).await? {
+            Some(done) => {
+                tracing::info!("⏩ Resuming backfill {} on chain {} after block {}", job.name, chain_id, done);
+                done + 1
+            }
+            None => start_block,
+        };
+
+        if from_block > to_block {
+            tracing::info!(
+                "✅ Backfill {} on chain {} already complete (from {} > to {})",
+                job.name,
+                chain_id,
+                from_block,
+                to_block
+            );
+            return Ok(());
+        }
+
+        let mut next_block = from_block;
+        loop {
+            match self
+                .index_range(
+                    chain_config,
+                    &contract_addresses,
+                    &event_signatures,
+                    next_block,
+                    to_block,
+                    &cursor,
+                    &sender,
+                )
+                .await
+            {
+                Ok(()) => break,
+                Err(e) if sender.is_closed() => return Err(e),
+                Err(e) => {
+                    tracing::error!("❌ Backfill {} on chain {} failed, retrying: {:?}", job.name, chain_id, e);
+                    sleep(Duration::from_secs(RETRY_DELAY_SECS)).await;
+                    if let Some(done) = self.load_checkpoint(chain_id, &cursor).await? {
+                        next_block = done + 1;
+                    }
+                }
+            }
+        }
+
+        tracing::info!(
+            "🏁 Backfill {} on chain {} finished blocks {}..={}",
+            job.name,
+            chain_id,
+            from_block,
+            to_block
+        );
+        Ok(())
+    }
+}
+
+fn select_contracts<'a>(chain_config: &'a ChainConfig, names: &[String]) -> anyhow::Result<Vec<&'a ContractConfig>> {
+    if names.is_empty() {
+        return Ok(chain_config.contracts.iter().collect());
+    }
+
+    let mut selected = Vec::new();
+    for name in names {
+        match chain_config.contracts.iter().find(|c| &c.name == name) {
+            Some(contract) => selected.push(contract),
+            None => bail!("unknown contract {} on chain {}", name, chain_config.chain_id),
+        }
+    }
+    Ok(selected)
+}
@@ -0,0 +1,56 @@
+use crate::{
+    cache::Cache,
+    listener::listener::{EventListener, SyncCursor},
+    model::user_op::UserOpMessage,
+    storage::Storage,
+};
+
+impl<S, C> EventListener<S, C>
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    /// **Last fully stored block of a cursor**
+    ///
+    /// The storage checkpoint is the source of truth, Redis only caches the live one and
+    /// is skipped when it is unavailable or was flushed.
+    pub(super) async fn load_checkpoint(&self, chain_id: u32, cursor: &SyncCursor) -> anyhow::Result<Option<u64>> {
+        if let SyncCursor::Live = cursor {
+            match self.app.cache.get_last_synced_block(chain_id).await {
+                Ok(Some(block_number)) => return Ok(Some(block_number)),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("⚠️ Redis checkpoint of chain {} unavailable, reading storage: {:?}", chain_id, e),
+            }
+        }
+
+        let checkpoint = self.app.storage.get_checkpoint(chain_id, &cursor.to_string()).await?;
+        if let (SyncCursor::Live, Some(block_number)) = (cursor, checkpoint) {
+            if let Err(e) = self.app.cache.set_last_synced_block(chain_id, block_number).await {
+                tracing::warn!("⚠️ Failed to cache checkpoint of chain {}: {:?}", chain_id, e);
+            }
+        }
+        Ok(checkpoint)
+    }
+
+    /// **Record a fully stored block with the range's staged user ops, in storage first so the cache never runs ahead of it**
+    pub(super) async fn save_checkpoint(
+        &self,
+        chain_id: u32,
+        cursor: &SyncCursor,
+        block_number: u64,
+        user_ops: Vec<UserOpMessage>,
+    ) -> anyhow::Result<()> {
+        self.app
+            .storage
+            .set_checkpoint(chain_id, &cursor.to_string(), block_number, user_ops)
+            .await?;
+
+        if let SyncCursor::Live = cursor {
+            if let Err(e) = self.app.cache.set_last_synced_block(chain_id, block_number).await {
+                tracing::warn!("⚠️ Failed to cache checkpoint of chain {}: {:?}", chain_id, e);
+            }
+        }
+        tracing::info!("✅ Chain {} {} checkpoint at block {}", chain_id, cursor, block_number);
+        Ok(())
+    }
+}
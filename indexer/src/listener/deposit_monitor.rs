@@ -0,0 +1,135 @@
+use std::str::FromStr;
+
+use alloy::{
+    primitives::{utils::parse_ether, Address, U256},
+    providers::Provider,
+    rpc::types::{BlockId, TransactionRequest},
+};
+use alloy_sol_types::SolCall;
+use crate::events::entry_point::balanceOfCall;
+
+use crate::{
+    cache::Cache,
+    config::config::ChainConfig,
+    listener::listener::{EventListener, SyncCursor},
+    metrics,
+    storage::Storage,
+    utils::u256_to_decimal,
+};
+
+impl<S, C> EventListener<S, C>
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    /// **Check the deposits of paymasters with a min_deposit against balanceOf and warn when one runs low**
+    ///
+    /// Balances are read at the live checkpoint so they line up with the indexed events,
+    /// which also corrects any drift left behind by reorgs.
+    pub async fn check_deposits(&self, chain_config: &ChainConfig) -> anyhow::Result<()> {
+        let chain_id = chain_config.chain_id;
+        let Some(block_number) = self.load_checkpoint(chain_id, &SyncCursor::Live).await? else {
+            tracing::debug!("⏳ Chain {} has no checkpoint yet, skipping deposit check", chain_id);
+            return Ok(());
+        };
+
+        // Only paymasters with a threshold are checked, in the EntryPoints they are configured with
+        for paymaster in chain_config.contracts.iter().filter(|c| c.min_deposit.is_some()) {
+            let Ok(paymaster_address) = Address::from_str(&paymaster.address) else {
+                continue;
+            };
+            let min_deposit = match paymaster.min_deposit.as_deref().map(parse_ether).transpose() {
+                Ok(Some(min_deposit)) => min_deposit,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::error!("❌ Invalid min_deposit for {} on chain {}: {:?}", paymaster.name, chain_id, e);
+                    continue;
+                }
+            };
+            if paymaster.entry_points.is_empty() {
+                tracing::warn!(
+                    "⚠️ {} on chain {} has a min_deposit but no entry_points, its deposit is not checked",
+                    paymaster.name,
+                    chain_id
+                );
+                continue;
+            }
+            let entry_points: Vec<Address> = paymaster
+                .entry_points
+                .iter()
+                .filter_map(|address| match Address::from_str(address) {
+                    Ok(address) => Some(address),
+                    Err(e) => {
+                        tracing::error!("❌ Invalid entry point {} for {} on chain {}: {:?}", address, paymaster.name, chain_id, e);
+                        None
+                    }
+                })
+                .collect();
+
+            for entry_point in &entry_points {
+                let deposit = self.balance_of(*entry_point, paymaster_address, block_number).await?;
+                let entry_point_str = format!("{:?}", entry_point);
+                let paymaster_str = format!("{:?}", paymaster_address);
+
+                let previous = self
+                    .app
+                    .storage
+                    .reconcile_deposit(chain_id, &entry_point_str, &paymaster_str, u256_to_decimal(deposit), block_number)
+                    .await?;
+                if let Some(previous) = previous.filter(|p| *p != u256_to_decimal(deposit)) {
+                    tracing::warn!(
+                        "⚖️ Deposit of {} in {} on chain {} drifted: indexed {} wei, on chain {} wei",
+                        paymaster.name,
+                        entry_point_str,
+                        chain_id,
+                        previous,
+                        deposit
+                    );
+                }
+
+                let chain_id_label = chain_id.to_string();
+                metrics::set_gauge(
+                    "indexer_paymaster_deposit_wei",
+                    "Paymaster deposit held by the EntryPoint",
+                    &[
+                        ("chain_id", &chain_id_label),
+                        ("entry_point", &entry_point_str),
+                        ("paymaster", &paymaster.name),
+                    ],
+                    f64::from(deposit),
+                );
+
+                if deposit < min_deposit {
+                    tracing::warn!(
+                        event = "paymaster_deposit_low",
+                        chain_id,
+                        paymaster = %paymaster_str,
+                        paymaster_name = %paymaster.name,
+                        entry_point = %entry_point_str,
+                        deposit_wei = %deposit,
+                        min_deposit_wei = %min_deposit,
+                        block_number,
+                        "🪫 Paymaster {} deposit on chain {} is below the threshold",
+                        paymaster.name,
+                        chain_id
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn balance_of(&self, entry_point: Address, account: Address, block_number: u64) -> anyhow::Result<U256> {
+        let request = TransactionRequest::default()
+            .to(entry_point)
+            .input(balanceOfCall { account }.abi_encode().into());
+        let output = self
+            .rpc
+            .call("eth_call", |provider| {
+                let request = request.clone();
+                async move { provider.call(&request).block(BlockId::number(block_number)).await }
+            })
+            .await?;
+        Ok(balanceOfCall::abi_decode_returns(&output, true)?._0)
+    }
+}
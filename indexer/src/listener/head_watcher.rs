@@ -0,0 +1,111 @@
+use alloy::{
+    network::Ethereum,
+    providers::{Provider, RootProvider, WsConnect},
+    rpc::client::RpcClient,
+};
+use futures_util::StreamExt;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::Notify, time::sleep};
+use url::Url;
+
+const RECONNECT_BASE_SECS: u64 = 1;
+const RECONNECT_MAX_SECS: u64 = 60;
+
+/// **Wakes the listener on every new head received over a WebSocket subscription**
+///
+/// While the socket is down the listener keeps polling on its regular interval, and
+/// the next fetch after a reconnect covers every block missed in between.
+pub struct HeadWatcher {
+    new_head: Arc<Notify>,
+    connected: Arc<AtomicBool>,
+}
+
+impl HeadWatcher {
+    /// **Subscribe to newHeads in a background task**
+    pub fn spawn(chain_id: u32, ws_url: String, stale_after: Duration) -> Self {
+        let new_head = Arc::new(Notify::new());
+        let connected = Arc::new(AtomicBool::new(false));
+
+        let task_new_head = Arc::clone(&new_head);
+        let task_connected = Arc::clone(&connected);
+        tokio::spawn(async move {
+            let label = Url::parse(&ws_url)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_string))
+                .unwrap_or_else(|| "unknown".to_string());
+            let mut attempt: u32 = 0;
+            loop {
+                match Self::watch(chain_id, &ws_url, stale_after, &task_new_head, &task_connected).await {
+                    Ok(()) => {
+                        attempt = 0;
+                        tracing::warn!(
+                            "🔌 newHeads subscription on {} for chain {} ended, falling back to polling",
+                            label,
+                            chain_id
+                        );
+                    }
+                    Err(e) => {
+                        attempt = attempt.saturating_add(1);
+                        tracing::warn!(
+                            "🔌 WebSocket {} for chain {} unavailable, falling back to polling: {}",
+                            label,
+                            chain_id,
+                            e
+                        );
+                    }
+                }
+                task_connected.store(false, Ordering::Relaxed);
+
+                let backoff = (RECONNECT_BASE_SECS << attempt.min(6)).min(RECONNECT_MAX_SECS);
+                sleep(Duration::from_secs(backoff)).await;
+            }
+        });
+
+        Self { new_head, connected }
+    }
+
+    /// Runs one subscription until the stream ends or goes quiet for `stale_after`
+    async fn watch(
+        chain_id: u32,
+        ws_url: &str,
+        stale_after: Duration,
+        new_head: &Notify,
+        connected: &AtomicBool,
+    ) -> anyhow::Result<()> {
+        let client = RpcClient::connect_pubsub(WsConnect::new(ws_url)).await?;
+        let provider: RootProvider<Ethereum> = RootProvider::new(client);
+        let mut heads = provider.subscribe_blocks().await?.into_stream();
+
+        connected.store(true, Ordering::Relaxed);
+        tracing::info!("🔌 Subscribed to newHeads for chain {}", chain_id);
+
+        loop {
+            match tokio::time::timeout(stale_after, heads.next()).await {
+                Ok(Some(header)) => {
+                    tracing::debug!("🧱 New head {} on chain {}", header.number, chain_id);
+                    new_head.notify_one();
+                }
+                Ok(None) => return Ok(()),
+                Err(_) => anyhow::bail!("no new head for {:?}", stale_after),
+            }
+        }
+    }
+
+    /// **Wait for the next head, or at most `poll_interval`**
+    pub async fn wait(&self, poll_interval: Duration) {
+        if !self.connected.load(Ordering::Relaxed) {
+            sleep(poll_interval).await;
+            return;
+        }
+        tokio::select! {
+            _ = self.new_head.notified() => {}
+            _ = sleep(poll_interval) => {}
+        }
+    }
+}
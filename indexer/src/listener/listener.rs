@@ -1,18 +1,49 @@
 use crate::{
-    app::AppContext, cache::Cache, config::config::ChainConfig, model::event::Event,
+    app::AppContext,
+    cache::Cache,
+    config::config::{ChainConfig, ContractConfig},
+    listener::rpc_pool::{is_block_range_error, RpcPool},
+    model::{event::{BatchAck, Event, TransactionInfo}, user_op::UserOpMessage},
     storage::Storage,
 };
-use alloy::rpc::types::{BlockId, BlockNumberOrTag, BlockTransactionsKind};
+use alloy::consensus::Transaction;
+use alloy_sol_types::SolEvent;
+use anyhow::Context;
+use crate::events::{entry_point::decode_bundle, events::UserOperationEvent};
+use alloy::rpc::types::{BlockId, BlockNumberOrTag, BlockTransactionsKind, Log};
 use alloy::{
-    network::Ethereum,
     primitives::{Address, B256},
-    providers::{Provider, RootProvider},
-    rpc::{client::RpcClient, types::Filter},
-    transports::http::Http,
+    providers::Provider,
+    rpc::types::Filter,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
-use std::{str::FromStr, sync::Arc};
 use tokio::sync::mpsc;
-use url::Url;
+
+/// **Where indexing progress is recorded**
+pub enum SyncCursor {
+    /// Live tip following with reorg tracking, cached in Redis as `sync_block:{chain_id}`
+    Live,
+    /// Historical backfill job with its own cursor
+    Backfill(String),
+}
+
+impl fmt::Display for SyncCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncCursor::Live => f.write_str("live"),
+            SyncCursor::Backfill(job) => write!(f, "backfill:{}", job),
+        }
+    }
+}
 
 /// **EventListener Struct**
 pub struct EventListener<S, C>
@@ -20,8 +51,10 @@ where
     S: Storage + Send + Sync + 'static,
     C: Cache + Send + Sync + 'static,
 {
-    provider: RootProvider<Ethereum>,
-    app: Arc<AppContext<S, C>>,
+    pub(super) rpc: RpcPool,
+    pub(super) app: Arc<AppContext<S, C>>,
+    block_range: AtomicU64, // current eth_getLogs block range, adapted to provider limits
+    direct_writes: AtomicBool, // set when a range's checkpoint commit failed, its retry writes user ops one by one
 }
 
 impl<S, C> EventListener<S, C>
@@ -30,19 +63,20 @@ where
     C: Cache + Send + Sync + 'static,
 {
     /// **Initialize the EventListener**
-    pub async fn new(rpc_url: &str, app: Arc<AppContext<S, C>>) -> Self {
-        let url = Url::parse(rpc_url).expect("Invalid RPC URL");
-
-        // **Initialize HTTP Transport**
-        let transport = Http::new(url);
-
-        // **Create RPC Client**
-        let rpc_client = RpcClient::new(transport, true);
-
-        // **Create RootProvider**
-        let provider = RootProvider::new(rpc_client);
+    pub async fn new(chain_config: &ChainConfig, app: Arc<AppContext<S, C>>) -> Self {
+        // **Create a provider per configured RPC endpoint**
+        let rpc = RpcPool::new(
+            chain_config.chain_id,
+            &chain_config.rpc_endpoints(),
+            Duration::from_secs(chain_config.rpc_timeout_secs),
+        );
 
-        Self { provider, app }
+        Self {
+            rpc,
+            app,
+            block_range: AtomicU64::new(chain_config.max_block_range.max(1)),
+            direct_writes: AtomicBool::new(false),
+        }
     }
 
     pub async fn listen_events(&self, chain_config: &ChainConfig, sender: mpsc::Sender<Event>) {
@@ -50,35 +84,99 @@ where
             return;
         }
 
-        // -- Contract addresses
-        let contract_addresses: Vec<Address> = chain_config
-            .contracts
-            .iter()
-            .map(|c| Address::from_str(&c.address).expect("Invalid contract address"))
-            .collect();
-
-        // -- Event signatures
-        let mut event_signatures: Vec<B256> = Vec::new();
         for contract in &chain_config.contracts {
             tracing::info!(
                 "-- Listening to Contract: {} on chainId: {}",
                 contract.name,
                 chain_config.chain_id
             );
-            for event in &contract.events {
-                event_signatures
-                    .push(B256::from_str(&event.signature).expect("Invalid event signature"));
+        }
+        let contracts: Vec<&ContractConfig> = chain_config.contracts.iter().collect();
+        let (contract_addresses, event_signatures) = log_filter_targets(&contracts);
+
+        self.rpc.log_health();
+
+        // -- Compute to_block
+        let Some(to_block) = self.safe_head(chain_config).await else {
+            return;
+        };
+
+        // -- Roll back indexed blocks that are no longer canonical
+        if let Err(e) = self.handle_reorg(chain_config).await {
+            tracing::error!(
+                "❌ Reorg check failed for chain {}: {:?}",
+                chain_config.chain_id,
+                e
+            );
+            return;
+        }
+
+        // -- Determine from_block
+        let from_block = match self.load_checkpoint(chain_config.chain_id, &SyncCursor::Live).await {
+            Ok(Some(last_synced)) => last_synced + 1,
+            Ok(None) => to_block.saturating_sub(chain_config.polling_blocks),
+            Err(e) => {
+                // Guessing a start block here would skip data, wait for the checkpoint instead
+                tracing::error!("❌ Failed to get last synced block: {:?}", e);
+                return;
             }
+        };
+
+        // Final catch-up check
+        if from_block > to_block {
+            tracing::info!(
+                "⏳ No new blocks to index for chain {} (from {} > to {})",
+                chain_config.chain_id,
+                from_block,
+                to_block
+            );
+            return;
         }
 
+        if let Err(e) = self
+            .index_range(
+                chain_config,
+                &contract_addresses,
+                &event_signatures,
+                from_block,
+                to_block,
+                &SyncCursor::Live,
+                &sender,
+            )
+            .await
+        {
+            tracing::error!("❌ Error fetching logs: {:?}", e);
+        }
+    }
+
+    /// **Latest block that is safe to index: finalized, or latest minus the reorg buffer**
+    pub(super) async fn safe_head(&self, chain_config: &ChainConfig) -> Option<u64> {
         // -- Get latest and finalized blocks
-        let latest_block_number = self.provider.get_block_number().await.unwrap();
+        let latest_block_number = match self
+            .rpc
+            .call("eth_blockNumber", |provider| async move { provider.get_block_number().await })
+            .await
+        {
+            Ok(block_number) => block_number,
+            Err(e) => {
+                tracing::error!(
+                    "❌ Failed to fetch latest block for chain {}: {:?}",
+                    chain_config.chain_id,
+                    e
+                );
+                return None;
+            }
+        };
         let finalized_block = if chain_config.use_finalized {
-            self.provider
-                .get_block(
-                    BlockId::Number(BlockNumberOrTag::Finalized),
-                    BlockTransactionsKind::Hashes,
-                )
+            self.rpc
+                .call("eth_getBlockByNumber", |provider| async move {
+                    provider
+                        .get_block(
+                            BlockId::Number(BlockNumberOrTag::Finalized),
+                            BlockTransactionsKind::Hashes,
+                        )
+                        .await
+                })
                 .await
                 .ok()
                 .flatten()
@@ -87,7 +185,6 @@ where
             None
         };
 
-        // -- Compute to_block
         let reorg_buffer = chain_config.reorg_buffer;
         let to_block =
             finalized_block.unwrap_or_else(|| latest_block_number.saturating_sub(reorg_buffer));
@@ -99,127 +196,396 @@ where
             reorg_buffer,
             chain_config.use_finalized
         );
+        Some(to_block)
+    }
 
-        // -- Determine from_block
-        let from_block = match self
-            .app
-            .cache
-            .get_last_synced_block(chain_config.chain_id)
-            .await
-        {
-            Ok(Some(last_synced)) => {
-                let next_block = last_synced + 1;
-                if next_block > to_block {
-                    tracing::info!(
-                        "⏳ No new blocks to index for chain {} (from {} > to {})",
-                        chain_config.chain_id,
-                        next_block,
-                        to_block
-                    );
-                    return;
-                }
-                next_block
-            }
-            Ok(None) => to_block.saturating_sub(chain_config.polling_blocks),
-            Err(e) => {
-                tracing::error!("Failed to get last synced block: {:?}", e);
-                to_block.saturating_sub(chain_config.polling_blocks)
-            }
-        };
-
-        // Final catch-up check
-        if from_block > to_block {
-            tracing::info!(
-                "⏳ No new blocks to index for chain {} (from {} > to {})",
-                chain_config.chain_id,
-                from_block,
-                to_block
-            );
-            return;
-        }
-
+    /// **Fetch and forward logs for a block range, chunk by chunk**
+    ///
+    /// Chunks adapt to provider limits and the cursor advances after each one, so a large
+    /// gap makes progress even if a later chunk fails.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) async fn index_range(
+        &self,
+        chain_config: &ChainConfig,
+        contract_addresses: &[Address],
+        event_signatures: &[B256],
+        from_block: u64,
+        to_block: u64,
+        cursor: &SyncCursor,
+        sender: &mpsc::Sender<Event>,
+    ) -> anyhow::Result<()> {
         tracing::info!(
-            "📦 Fetching logs for chain {} from block {} to {} (contracts: {}, events: {})",
+            "📦 Fetching logs for chain {} from block {} to {} (cursor: {}, contracts: {}, events: {})",
             chain_config.chain_id,
             from_block,
             to_block,
+            cursor,
             contract_addresses.len(),
             event_signatures.len()
         );
 
-        // -- Build log filter
-        let filter = Filter::new()
-            .address(contract_addresses)
-            .event_signature(event_signatures)
-            .from_block(from_block)
-            .to_block(to_block);
+        // Configs built without Config::load are not clamped, a zero range would underflow below
+        let max_block_range = chain_config.max_block_range.max(1);
+        let mut chunk_from = from_block;
+        while chunk_from <= to_block {
+            let block_range = self.block_range.load(Ordering::Relaxed).max(1);
+            let chunk_to = chunk_from.saturating_add(block_range - 1).min(to_block);
+
+            // Read the live tip's hash before its logs, a reorg in between then shows up as a mismatch on the next poll
+            let to_block_hash = match cursor {
+                SyncCursor::Live => self.tip_hash(chunk_to).await,
+                SyncCursor::Backfill(_) => None,
+            };
 
-        // -- Retrieve logs and process
-        match self.provider.get_logs(&filter).await {
-            Ok(logs) => {
-                let mut max_block_seen: Option<u64> = None;
+            let filter = Filter::new()
+                .address(contract_addresses.to_vec())
+                .event_signature(event_signatures.to_vec())
+                .from_block(chunk_from)
+                .to_block(chunk_to);
 
-                if logs.is_empty() {
+            let logs = self
+                .rpc
+                .call("eth_getLogs", |provider| {
+                    let filter = filter.clone();
+                    async move { provider.get_logs(&filter).await }
+                })
+                .await;
+
+            match logs {
+                Ok(logs) => {
+                    self.index_chunk(chain_config, chunk_from, chunk_to, to_block_hash, logs, cursor, sender)
+                        .await?;
+                    chunk_from = chunk_to + 1;
+
+                    // Grow back towards the configured range after successful calls
+                    let grown = block_range.saturating_mul(2).min(max_block_range);
+                    if grown != block_range {
+                        self.block_range.store(grown, Ordering::Relaxed);
+                        tracing::debug!("📈 Block range for chain {} grown to {}", chain_config.chain_id, grown);
+                    }
+                }
+                Err(e) if block_range > 1 && is_block_range_error(&e) => {
+                    let shrunk = (block_range / 2).max(1);
+                    self.block_range.store(shrunk, Ordering::Relaxed);
                     tracing::warn!(
-                        "⚠️ No logs returned for chain {} from {} to {} — advancing anyway",
+                        "✂️ Provider rejected blocks {}..{} on chain {}, shrinking block range {} -> {}: {}",
+                        chunk_from,
+                        chunk_to,
                         chain_config.chain_id,
-                        from_block,
-                        to_block
+                        block_range,
+                        shrunk,
+                        e
                     );
-                    max_block_seen = Some(to_block);
                 }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
 
-                for log in logs {
-                    tracing::debug!("ChainID: {}, Log: {:?}", chain_config.chain_id, log);
+    /// **Send a chunk's logs to the processor and advance the cursor past it**
+    #[allow(clippy::too_many_arguments)]
+    async fn index_chunk(
+        &self,
+        chain_config: &ChainConfig,
+        from_block: u64,
+        to_block: u64,
+        to_block_hash: Option<String>,
+        logs: Vec<Log>,
+        cursor: &SyncCursor,
+        sender: &mpsc::Sender<Event>,
+    ) -> anyhow::Result<()> {
+        if logs.is_empty() {
+            tracing::warn!(
+                "⚠️ No logs returned for chain {} from {} to {} — advancing anyway",
+                chain_config.chain_id,
+                from_block,
+                to_block
+            );
+        }
+
+        let ack = if self.direct_writes.swap(false, Ordering::Relaxed) {
+            BatchAck::direct(logs.len())
+        } else {
+            BatchAck::new(logs.len())
+        };
+        // Block hashes seen while indexing, checked for reorgs on the next poll. Log hashes come later and win
+        // over the tip's header hash, they are the fork that was actually indexed.
+        let mut indexed_hashes: Vec<(u64, String)> = to_block_hash.map(|hash| (to_block, hash)).into_iter().collect();
+        // Header timestamps fetched for this chunk, one lookup per block
+        let mut block_timestamps: HashMap<u64, u64> = HashMap::new();
+        // Bundle transactions of UserOperationEvent logs, one lookup per transaction
+        let mut transactions: HashMap<B256, Option<Arc<TransactionInfo>>> = HashMap::new();
+
+        for log in logs {
+            tracing::debug!("ChainID: {}, Log: {:?}", chain_config.chain_id, log);
 
-                    if let Some(block_number) = log.block_number {
-                        max_block_seen =
-                            Some(max_block_seen.map_or(block_number, |max| max.max(block_number)));
-                    } else {
-                        tracing::warn!(
-                            "⚠️ Log missing block_number — cannot use for sync progress"
-                        );
+            let block_timestamp = match (log.block_timestamp, log.block_number) {
+                (Some(timestamp), _) => Some(timestamp),
+                (None, Some(block_number)) => match block_timestamps.get(&block_number) {
+                    Some(timestamp) => Some(*timestamp),
+                    None => {
+                        let timestamp = self.block_timestamp(block_number).await?;
+                        block_timestamps.insert(block_number, timestamp);
+                        Some(timestamp)
                     }
+                },
+                (None, None) => None,
+            };
 
-                    if sender
-                        .send(Event {
-                            chain_id: chain_config.chain_id,
-                            log,
-                        })
-                        .await
-                        .is_err()
-                    {
-                        tracing::error!(
-                            "❌ Failed to send log to processor. Aborting block update."
-                        );
-                        return;
+            let transaction = match log.transaction_hash {
+                Some(tx_hash) if log.topic0() == Some(&UserOperationEvent::SIGNATURE_HASH) => {
+                    match transactions.get(&tx_hash) {
+                        Some(info) => info.clone(),
+                        None => {
+                            let info = self.transaction_info(tx_hash).await?.map(Arc::new);
+                            transactions.insert(tx_hash, info.clone());
+                            info
+                        }
                     }
                 }
+                _ => None,
+            };
 
-                if let Some(max_block) = max_block_seen {
-                    if let Err(e) = self
-                        .app
-                        .cache
-                        .set_last_synced_block(chain_config.chain_id, max_block)
-                        .await
-                    {
-                        tracing::error!(
-                            "⚠️ Failed to set last synced block to {}: {:?}",
-                            max_block,
-                            e
-                        );
-                    } else {
-                        tracing::info!(
-                            "✅ Updated last synced block for chain {} to {}",
-                            chain_config.chain_id,
-                            max_block
-                        );
-                    }
+            match (log.block_number, log.block_hash) {
+                (Some(block_number), Some(block_hash)) => {
+                    indexed_hashes.push((block_number, format!("{:?}", block_hash)));
                 }
+                _ => tracing::warn!(
+                    "⚠️ Log missing block_number or block_hash — cannot use for reorg checks"
+                ),
+            }
+
+            if sender
+                .send(Event {
+                    chain_id: chain_config.chain_id,
+                    log,
+                    block_timestamp,
+                    transaction,
+                    ack: Some(Arc::clone(&ack)),
+                })
+                .await
+                .is_err()
+            {
+                anyhow::bail!("processor channel closed, aborting block update");
+            }
+        }
+
+        // Progress is only recorded once every event of the range is stored, so a failure is indexed again
+        if !ack.wait(sender).await {
+            anyhow::bail!(
+                "events of blocks {}..={} on chain {} were not all stored",
+                from_block,
+                to_block,
+                chain_config.chain_id
+            );
+        }
+
+        // The range's user ops are committed in the checkpoint's transaction
+        let user_ops = ack.take_user_ops();
+        let staged = !user_ops.is_empty();
+        let result = match cursor {
+            SyncCursor::Live => self.save_live_progress(chain_config, to_block, indexed_hashes, user_ops).await,
+            SyncCursor::Backfill(_) => self.save_checkpoint(chain_config.chain_id, cursor, to_block, user_ops).await,
+        };
+        if result.is_err() && staged {
+            // One bad op fails the whole commit, written one by one it fails its own event and can be dead-lettered
+            self.direct_writes.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+
+    async fn save_live_progress(
+        &self,
+        chain_config: &ChainConfig,
+        to_block: u64,
+        indexed_hashes: Vec<(u64, String)>,
+        user_ops: Vec<UserOpMessage>,
+    ) -> anyhow::Result<()> {
+        if let Err(e) = self
+            .app
+            .cache
+            .record_block_hashes(
+                chain_config.chain_id,
+                &indexed_hashes,
+                to_block.saturating_sub(chain_config.reorg_window),
+            )
+            .await
+        {
+            tracing::error!("⚠️ Failed to record indexed block hashes: {:?}", e);
+        }
+
+        self.save_checkpoint(chain_config.chain_id, &SyncCursor::Live, to_block, user_ops).await
+    }
+
+    /// **Detect reorged blocks and roll back what was indexed from them**
+    async fn handle_reorg(&self, chain_config: &ChainConfig) -> anyhow::Result<()> {
+        let chain_id = chain_config.chain_id;
+        let indexed = self.app.cache.get_block_hashes(chain_id).await?;
+
+        let Some(ancestor) = self.find_common_ancestor(&indexed).await? else {
+            return Ok(());
+        };
+        let from_block = ancestor + 1;
+        tracing::warn!(
+            "🔀 Reorg detected on chain {}: common ancestor {}, rolling back from block {}",
+            chain_id,
+            ancestor,
+            from_block
+        );
+
+        // The storage checkpoint is rewound with the rows, drop the cached one so it is not read instead
+        self.app.cache.discard_last_synced_block(chain_id).await?;
+
+        let rolled_back = self.app.storage.rollback_from_block(chain_id, from_block).await?;
+        for user_op_hash in &rolled_back {
+            if let Err(e) = self.app.cache.revert_userop_usage(user_op_hash).await {
+                tracing::error!("❌ Failed to revert usage for {}: {:?}", user_op_hash, e);
+            }
+        }
+
+        self.app.cache.discard_block_hashes_from(chain_id, from_block).await?;
+
+        tracing::info!(
+            "↩️ Rolled back {} user ops on chain {}, re-indexing from block {}",
+            rolled_back.len(),
+            chain_id,
+            from_block
+        );
+        Ok(())
+    }
+
+    /// **Hash of the last block of a live chunk, `None` (with a warning) when it cannot be read**
+    async fn tip_hash(&self, block_number: u64) -> Option<String> {
+        match self.block_hash(block_number).await {
+            Ok(Some(hash)) => Some(hash),
+            Ok(None) => {
+                tracing::warn!("⚠️ Block {} not found while recording its hash", block_number);
+                None
             }
             Err(e) => {
-                tracing::error!("❌ Error fetching logs: {:?}", e);
+                tracing::warn!("⚠️ Failed to fetch hash of block {}: {:?}", block_number, e);
+                None
+            }
+        }
+    }
+
+    /// Walks indexed blocks from newest to oldest and returns the newest one that is still canonical.
+    /// Returns `None` when the newest indexed block is still canonical.
+    async fn find_common_ancestor(&self, indexed: &[(u64, String)]) -> anyhow::Result<Option<u64>> {
+        for (depth, (block_number, indexed_hash)) in indexed.iter().rev().enumerate() {
+            let canonical_hash = self.block_hash(*block_number).await?;
+            if canonical_hash.as_deref() == Some(indexed_hash.as_str()) {
+                return Ok((depth > 0).then_some(*block_number));
+            }
+            tracing::warn!(
+                "⚠️ Block {} hash changed: indexed {}, canonical {:?}",
+                block_number,
+                indexed_hash,
+                canonical_hash
+            );
+        }
+
+        // The reorg is deeper than the remembered window, restart right below it
+        Ok(indexed.first().map(|(block_number, _)| block_number.saturating_sub(1)))
+    }
+
+    /// **Bundler, gas price and calldata of the transaction that emitted a log**
+    pub(crate) async fn transaction_info(&self, tx_hash: B256) -> anyhow::Result<Option<TransactionInfo>> {
+        let receipt = self
+            .rpc
+            .call("eth_getTransactionReceipt", |provider| async move {
+                provider.get_transaction_receipt(tx_hash).await
+            })
+            .await?;
+        let transaction = self
+            .rpc
+            .call("eth_getTransactionByHash", |provider| async move {
+                provider.get_transaction_by_hash(tx_hash).await
+            })
+            .await?;
+
+        let (Some(receipt), Some(transaction)) = (receipt, transaction) else {
+            tracing::warn!("⚠️ Transaction {:?} or its receipt not found, skipping enrichment", tx_hash);
+            return Ok(None);
+        };
+
+        Ok(Some(TransactionInfo {
+            bundler: receipt.from,
+            effective_gas_price: receipt.effective_gas_price,
+            bundle: decode_bundle(transaction.input()),
+        }))
+    }
+
+    /// **Logs of a transaction that the chain's configured contracts and events would have fetched**
+    pub(crate) async fn receipt_logs(&self, chain_config: &ChainConfig, tx_hash: B256) -> anyhow::Result<Vec<Log>> {
+        let receipt = self
+            .rpc
+            .call("eth_getTransactionReceipt", |provider| async move {
+                provider.get_transaction_receipt(tx_hash).await
+            })
+            .await?
+            .with_context(|| format!("Receipt of transaction {:?} not found", tx_hash))?;
+
+        let contracts: Vec<&ContractConfig> = chain_config.contracts.iter().collect();
+        let (contract_addresses, event_signatures) = log_filter_targets(&contracts);
+        Ok(receipt
+            .inner
+            .logs()
+            .iter()
+            .filter(|log| contract_addresses.contains(&log.address()))
+            .filter(|log| log.topic0().is_some_and(|topic| event_signatures.contains(topic)))
+            .cloned()
+            .collect())
+    }
+
+    async fn block_timestamp(&self, block_number: u64) -> anyhow::Result<u64> {
+        let block = self
+            .rpc
+            .call("eth_getBlockByNumber", |provider| async move {
+                provider
+                    .get_block(
+                        BlockId::Number(BlockNumberOrTag::Number(block_number)),
+                        BlockTransactionsKind::Hashes,
+                    )
+                    .await
+            })
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("block {} not found", block_number))?;
+        Ok(block.header.inner.timestamp)
+    }
+
+    async fn block_hash(&self, block_number: u64) -> anyhow::Result<Option<String>> {
+        let block = self
+            .rpc
+            .call("eth_getBlockByNumber", |provider| async move {
+                provider
+                    .get_block(
+                        BlockId::Number(BlockNumberOrTag::Number(block_number)),
+                        BlockTransactionsKind::Hashes,
+                    )
+                    .await
+            })
+            .await?;
+        Ok(block.map(|b| format!("{:?}", b.header.hash)))
+    }
+}
+
+/// **Contract addresses and event signatures to filter logs on**
+pub(super) fn log_filter_targets(contracts: &[&ContractConfig]) -> (Vec<Address>, Vec<B256>) {
+    let contract_addresses: Vec<Address> = contracts
+        .iter()
+        .map(|c| Address::from_str(&c.address).expect("Invalid contract address"))
+        .collect();
+
+    let mut event_signatures: Vec<B256> = Vec::new();
+    for contract in contracts {
+        for event in &contract.events {
+            let signature = B256::from_str(&event.signature).expect("Invalid event signature");
+            if !event_signatures.contains(&signature) {
+                event_signatures.push(signature);
             }
         }
     }
+    (contract_addresses, event_signatures)
 }
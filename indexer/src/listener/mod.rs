@@ -1,2 +1,7 @@
-
-pub mod listener;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod listener;
+pub mod rpc_pool;
+pub mod head_watcher;
+pub mod backfill;
+pub mod checkpoint;
+pub mod deposit_monitor;
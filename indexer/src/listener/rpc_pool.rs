@@ -0,0 +1,276 @@
+use alloy::{
+    network::Ethereum,
+    providers::RootProvider,
+    rpc::client::RpcClient,
+    transports::{http::Http, RpcError, TransportError, TransportErrorKind},
+};
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use url::Url;
+
+use crate::metrics;
+
+// Weight of the newest sample in the latency moving average
+const LATENCY_EWMA_ALPHA: f64 = 0.3;
+// Consecutive failures before an endpoint is taken out of rotation
+const MAX_CONSECUTIVE_ERRORS: u32 = 3;
+const BASE_COOLDOWN_SECS: u64 = 10;
+const MAX_COOLDOWN_SECS: u64 = 300;
+
+// eth_getLogs error messages of known providers when a block range or result set is too large
+const BLOCK_RANGE_ERROR_HINTS: [&str; 9] = [
+    "query returned more than",    // Infura, geth-based nodes
+    "log response size exceeded",  // Alchemy
+    "query timeout exceeded",      // Alchemy, on ranges too heavy to scan
+    "exceed maximum block range",  // NodeReal, BSC nodes
+    "block range is too wide",     // Ankr
+    "block range limit exceeded",  // Chainstack
+    "block range too large",       // Erigon
+    "query exceeds max results",   // Erigon
+    "is limited to a",             // QuickNode, "eth_getLogs is limited to a 10,000 range"
+];
+
+#[derive(Debug, Default)]
+struct EndpointHealth {
+    latency_ms: Option<f64>,
+    requests: u64,
+    errors: u64,
+    consecutive_errors: u32,
+    cooldown_until: Option<Instant>,
+}
+
+impl EndpointHealth {
+    fn is_available(&self, now: Instant) -> bool {
+        self.cooldown_until.map_or(true, |until| now >= until)
+    }
+
+    /// Lower is better: latency plus a penalty for recent errors
+    fn score(&self) -> f64 {
+        let latency = self.latency_ms.unwrap_or(0.0);
+        let error_rate = if self.requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.requests as f64
+        };
+        latency * (1.0 + self.consecutive_errors as f64) + error_rate * 1000.0
+    }
+}
+
+struct RpcEndpoint {
+    label: String, // host only, RPC URLs often embed API keys
+    provider: RootProvider<Ethereum>,
+    health: Mutex<EndpointHealth>,
+}
+
+/// **Pool of RPC endpoints for one chain, routing calls to the healthiest one**
+pub struct RpcPool {
+    chain_id: u32,
+    endpoints: Vec<RpcEndpoint>,
+    timeout: Duration,
+}
+
+impl RpcPool {
+    pub fn new(chain_id: u32, rpc_urls: &[String], timeout: Duration) -> Self {
+        let endpoints: Vec<RpcEndpoint> = rpc_urls
+            .iter()
+            .enumerate()
+            .map(|(index, rpc_url)| {
+                let url = Url::parse(rpc_url).expect("Invalid RPC URL");
+                let label = format!("{}#{}", url.host_str().unwrap_or("unknown"), index);
+                let rpc_client = RpcClient::new(Http::new(url), true);
+                RpcEndpoint {
+                    label,
+                    provider: RootProvider::new(rpc_client),
+                    health: Mutex::new(EndpointHealth::default()),
+                }
+            })
+            .collect();
+        assert!(!endpoints.is_empty(), "No RPC endpoints configured for chain {}", chain_id);
+
+        Self { chain_id, endpoints, timeout }
+    }
+
+    /// **Run an RPC call, failing over to the next healthiest endpoint on transport errors**
+    ///
+    /// JSON-RPC error responses are returned as-is: the node answered, so another endpoint
+    /// would most likely answer the same way.
+    pub async fn call<T, F, Fut>(&self, method: &str, f: F) -> Result<T, TransportError>
+    where
+        F: Fn(RootProvider<Ethereum>) -> Fut,
+        Fut: Future<Output = Result<T, TransportError>>,
+    {
+        let mut last_error = None;
+        for (attempt, index) in self.ranked_endpoints().into_iter().enumerate() {
+            let endpoint = &self.endpoints[index];
+            if attempt > 0 {
+                tracing::warn!(
+                    "🔁 Failing over {} on chain {} to {}",
+                    method,
+                    self.chain_id,
+                    endpoint.label
+                );
+                metrics::inc_counter(
+                    "indexer_rpc_failovers_total",
+                    "RPC calls retried on another endpoint",
+                    &[("chain_id", &self.chain_id.to_string()), ("endpoint", &endpoint.label)],
+                    1.0,
+                );
+            }
+
+            let started = Instant::now();
+            let result = match tokio::time::timeout(self.timeout, f(endpoint.provider.clone())).await {
+                Ok(result) => result,
+                Err(_) => Err(TransportErrorKind::custom_str(&format!(
+                    "{} timed out after {:?}",
+                    method, self.timeout
+                ))),
+            };
+            let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+            match result {
+                Ok(value) => {
+                    self.record_success(endpoint, elapsed_ms);
+                    return Ok(value);
+                }
+                Err(RpcError::ErrorResp(payload))
+                    if !payload.is_retry_err() || is_block_range_message(&payload.message) =>
+                {
+                    self.record_success(endpoint, elapsed_ms);
+                    return Err(RpcError::ErrorResp(payload));
+                }
+                Err(e) => {
+                    self.record_error(endpoint, method, &e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| TransportErrorKind::custom_str("no RPC endpoint available")))
+    }
+
+    /// Available endpoints by score, then the ones cooling down as a last resort
+    fn ranked_endpoints(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let mut available: Vec<(usize, f64)> = Vec::new();
+        let mut cooling: Vec<(usize, Instant)> = Vec::new();
+        for (index, endpoint) in self.endpoints.iter().enumerate() {
+            let health = endpoint.health.lock().unwrap_or_else(|e| e.into_inner());
+            match health.cooldown_until {
+                Some(until) if !health.is_available(now) => cooling.push((index, until)),
+                _ => available.push((index, health.score())),
+            }
+        }
+        available.sort_by(|a, b| a.1.total_cmp(&b.1));
+        cooling.sort_by_key(|(_, until)| *until);
+
+        available
+            .into_iter()
+            .map(|(index, _)| index)
+            .chain(cooling.into_iter().map(|(index, _)| index))
+            .collect()
+    }
+
+    fn record_success(&self, endpoint: &RpcEndpoint, elapsed_ms: f64) {
+        let mut health = endpoint.health.lock().unwrap_or_else(|e| e.into_inner());
+        health.requests += 1;
+        health.latency_ms = Some(match health.latency_ms {
+            Some(avg) => avg + LATENCY_EWMA_ALPHA * (elapsed_ms - avg),
+            None => elapsed_ms,
+        });
+        if health.cooldown_until.take().is_some() {
+            tracing::info!("💚 RPC endpoint {} on chain {} recovered", endpoint.label, self.chain_id);
+        }
+        health.consecutive_errors = 0;
+        self.export_health(endpoint, &health, false);
+    }
+
+    fn record_error(&self, endpoint: &RpcEndpoint, method: &str, err: &TransportError) {
+        let mut health = endpoint.health.lock().unwrap_or_else(|e| e.into_inner());
+        health.requests += 1;
+        health.errors += 1;
+        health.consecutive_errors += 1;
+        tracing::warn!(
+            "⚠️ RPC {} failed on {} (chain {}, {} consecutive errors): {}",
+            method,
+            endpoint.label,
+            self.chain_id,
+            health.consecutive_errors,
+            err
+        );
+
+        if health.consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+            // Back off exponentially while the endpoint keeps failing
+            let exponent = (health.consecutive_errors - MAX_CONSECUTIVE_ERRORS).min(5);
+            let cooldown = (BASE_COOLDOWN_SECS << exponent).min(MAX_COOLDOWN_SECS);
+            health.cooldown_until = Some(Instant::now() + Duration::from_secs(cooldown));
+            tracing::error!(
+                "🚫 RPC endpoint {} on chain {} marked unhealthy for {}s",
+                endpoint.label,
+                self.chain_id,
+                cooldown
+            );
+        }
+        self.export_health(endpoint, &health, true);
+    }
+
+    fn export_health(&self, endpoint: &RpcEndpoint, health: &EndpointHealth, failed: bool) {
+        let chain_id = self.chain_id.to_string();
+        let labels = [("chain_id", chain_id.as_str()), ("endpoint", endpoint.label.as_str())];
+        let up = if health.is_available(Instant::now()) { 1.0 } else { 0.0 };
+
+        metrics::inc_counter("indexer_rpc_requests_total", "RPC requests sent to the endpoint", &labels, 1.0);
+        if failed {
+            metrics::inc_counter("indexer_rpc_errors_total", "RPC requests that failed on the endpoint", &labels, 1.0);
+        }
+
+        metrics::set_gauge("indexer_rpc_endpoint_up", "Whether the RPC endpoint is in rotation", &labels, up);
+        metrics::set_gauge(
+            "indexer_rpc_endpoint_latency_ms",
+            "Moving average RPC latency in milliseconds",
+            &labels,
+            health.latency_ms.unwrap_or(0.0),
+        );
+    }
+
+    /// **Log a one-line health summary per endpoint**
+    pub fn log_health(&self) {
+        let now = Instant::now();
+        for endpoint in &self.endpoints {
+            let health = endpoint.health.lock().unwrap_or_else(|e| e.into_inner());
+            tracing::debug!(
+                "🩺 RPC {} chain {}: available={} latency_ms={:.1} requests={} errors={} consecutive_errors={}",
+                endpoint.label,
+                self.chain_id,
+                health.is_available(now),
+                health.latency_ms.unwrap_or(0.0),
+                health.requests,
+                health.errors,
+                health.consecutive_errors
+            );
+        }
+    }
+}
+
+/// **Whether a get_logs error means the block range should be narrowed**
+///
+/// Only the provider's own answers count: timeouts and connection errors are endpoint health issues.
+pub fn is_block_range_error(err: &TransportError) -> bool {
+    match err {
+        // -32005 is the common "limit exceeded" code, Infura also answers rate limiting with it
+        RpcError::ErrorResp(payload) => {
+            (payload.code == -32005 && !payload.message.to_lowercase().contains("rate"))
+                || is_block_range_message(&payload.message)
+        }
+        // Some gateways reject a too large response body outright
+        RpcError::Transport(TransportErrorKind::HttpError(http)) => http.status == 413,
+        _ => false,
+    }
+}
+
+fn is_block_range_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    BLOCK_RANGE_ERROR_HINTS.iter().any(|hint| message.contains(hint))
+}
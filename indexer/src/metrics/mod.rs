@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+
+use warp::Filter;
+
+#[derive(Clone, Copy, PartialEq)]
+enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+impl MetricKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+struct MetricFamily {
+    kind: MetricKind,
+    help: &'static str,
+    // rendered label set -> value
+    series: BTreeMap<String, f64>,
+}
+
+fn registry() -> &'static Mutex<BTreeMap<&'static str, MetricFamily>> {
+    static REGISTRY: OnceLock<Mutex<BTreeMap<&'static str, MetricFamily>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    let rendered: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    rendered.join(",")
+}
+
+fn update(
+    name: &'static str,
+    help: &'static str,
+    kind: MetricKind,
+    labels: &[(&str, &str)],
+    apply: impl FnOnce(&mut f64),
+) {
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let family = registry.entry(name).or_insert_with(|| MetricFamily {
+        kind,
+        help,
+        series: BTreeMap::new(),
+    });
+    apply(family.series.entry(render_labels(labels)).or_insert(0.0));
+}
+
+/// **Set a gauge to an absolute value**
+pub fn set_gauge(name: &'static str, help: &'static str, labels: &[(&str, &str)], value: f64) {
+    update(name, help, MetricKind::Gauge, labels, |v| *v = value);
+}
+
+/// **Increment a counter**
+pub fn inc_counter(name: &'static str, help: &'static str, labels: &[(&str, &str)], by: f64) {
+    update(name, help, MetricKind::Counter, labels, |v| *v += by);
+}
+
+/// **Render all metrics in the Prometheus text format**
+pub fn render() -> String {
+    let registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let mut out = String::new();
+    for (name, family) in registry.iter() {
+        let _ = writeln!(out, "# HELP {} {}", name, family.help);
+        let _ = writeln!(out, "# TYPE {} {}", name, family.kind.as_str());
+        for (labels, value) in &family.series {
+            if labels.is_empty() {
+                let _ = writeln!(out, "{} {}", name, value);
+            } else {
+                let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+            }
+        }
+    }
+    out
+}
+
+/// **Serve `/metrics` for Prometheus scraping**
+pub async fn serve(port: u16) {
+    let route = warp::path("metrics")
+        .and(warp::get())
+        .map(|| warp::reply::with_header(render(), "content-type", "text/plain; version=0.0.4"));
+
+    tracing::info!("📊 Serving metrics on 0.0.0.0:{}/metrics", port);
+    warp::serve(route).run(([0, 0, 0, 0], port)).await;
+}
@@ -0,0 +1,18 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// Log of a config-only event, decoded from the `abi` of its `EventConfig`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractEvent {
+    pub chain_id: u32,
+    pub contract_address: String,
+    pub event_name: String,
+    pub event_signature: String,
+    pub block_number: u64,
+    pub block_hash: Option<String>,
+    pub tx_hash: Option<String>,
+    pub log_index: u64,
+    pub args: Value,
+    pub timestamp: String,
+}
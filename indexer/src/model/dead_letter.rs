@@ -0,0 +1,112 @@
+use std::fmt;
+
+use alloy::rpc::types::Log;
+use serde::{Deserialize, Serialize};
+
+use crate::model::event::Event;
+
+/// Input that can never be handled as is, e.g. a log that does not decode.
+/// It is dead-lettered right away instead of being retried.
+#[derive(Debug)]
+pub struct UnprocessableInput(pub String);
+
+impl fmt::Display for UnprocessableInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UnprocessableInput {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadLetterSource {
+    Log,   // A chain log, replayed through the event handlers
+    Kafka, // A Kafka payload, replayed through the consumer
+}
+
+impl DeadLetterSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeadLetterSource::Log => "log",
+            DeadLetterSource::Kafka => "kafka",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DeadLetterStage {
+    Decode, // The log did not decode
+    Parse,  // The Kafka payload is not a UserOpMessage
+    Store,  // Storage rejected the result
+}
+
+impl DeadLetterStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeadLetterStage::Decode => "decode",
+            DeadLetterStage::Parse => "parse",
+            DeadLetterStage::Store => "store",
+        }
+    }
+}
+
+/// Failed input, keyed so that every failure of the same input counts as an attempt
+#[derive(Debug)]
+pub struct DeadLetter {
+    pub source: DeadLetterSource,
+    pub entry_key: String,
+    pub chain_id: Option<u32>,
+    pub block_number: Option<u64>,
+    pub log_index: Option<u64>,
+    pub stage: DeadLetterStage,
+    pub error: String,
+    pub payload: String,
+}
+
+/// Log as kept in the dead-letter table, the bundle transaction is fetched again on replay
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredLog {
+    pub chain_id: u32,
+    pub log: Log,
+    pub block_timestamp: Option<u64>,
+}
+
+impl DeadLetter {
+    /// **Key of a chain log: chain, transaction and log index**
+    pub fn entry_key(event: &Event) -> String {
+        format!(
+            "{}:{:?}:{}",
+            event.chain_id,
+            event.log.transaction_hash.unwrap_or_default(),
+            event.log.log_index.unwrap_or_default()
+        )
+    }
+
+    pub fn from_event(event: &Event, stage: DeadLetterStage, error: &anyhow::Error) -> anyhow::Result<Self> {
+        let stored = StoredLog {
+            chain_id: event.chain_id,
+            log: event.log.clone(),
+            block_timestamp: event.block_timestamp,
+        };
+        Ok(Self {
+            source: DeadLetterSource::Log,
+            entry_key: Self::entry_key(event),
+            chain_id: Some(event.chain_id),
+            block_number: event.log.block_number,
+            log_index: event.log.log_index,
+            stage,
+            error: format!("{:?}", error),
+            payload: serde_json::to_string(&stored)?,
+        })
+    }
+}
+
+/// Unresolved dead letter, as read for a replay
+#[derive(Debug)]
+pub struct DeadLetterRecord {
+    pub id: i64,
+    pub source: String,
+    pub entry_key: String,
+    pub payload: String,
+    pub attempts: i32,
+}
@@ -1,8 +1,111 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
 
+use alloy::primitives::Address;
 use alloy::rpc::types::Log;
+use tokio::sync::{mpsc, Notify};
+
+use crate::events::entry_point::Bundle;
+use crate::model::user_op::UserOpMessage;
 
 #[derive(Clone)]
 pub struct Event {
     pub chain_id: u32,
-    pub log: Log
+    pub log: Log,
+    pub block_timestamp: Option<u64>, // seconds, from the log or its block header
+    pub transaction: Option<Arc<TransactionInfo>>, // only fetched for UserOperationEvent logs
+    pub ack: Option<Arc<BatchAck>>, // shared by the events of one indexed block range
+}
+
+impl Event {
+    /// **Report the event as stored, or not, to whoever is waiting on its batch**
+    pub fn complete(&self, stored: bool) {
+        if let Some(ack) = &self.ack {
+            ack.complete(stored);
+        }
+    }
+}
+
+/// **Counts the events of a block range until the processor has handled all of them**
+///
+/// User ops of the range are staged here and written with the range's checkpoint, in one transaction.
+pub struct BatchAck {
+    remaining: AtomicUsize,
+    failed: AtomicBool,
+    done: Notify,
+    user_ops: Option<Mutex<Vec<UserOpMessage>>>, // None when the handlers write user ops themselves
+}
+
+impl BatchAck {
+    pub fn new(events: usize) -> Arc<Self> {
+        Self::build(events, Some(Mutex::new(Vec::new())))
+    }
+
+    /// **A batch whose user ops are written by the handlers, one by one**
+    ///
+    /// Used to retry a range whose checkpoint commit failed, so a bad op fails its own event.
+    pub fn direct(events: usize) -> Arc<Self> {
+        Self::build(events, None)
+    }
+
+    fn build(events: usize, user_ops: Option<Mutex<Vec<UserOpMessage>>>) -> Arc<Self> {
+        Arc::new(Self {
+            remaining: AtomicUsize::new(events),
+            failed: AtomicBool::new(false),
+            done: Notify::new(),
+            user_ops,
+        })
+    }
+
+    /// **Stage a user op for the checkpoint commit, handing it back when the batch writes directly**
+    pub fn stage_user_op(&self, msg: UserOpMessage) -> Option<UserOpMessage> {
+        match &self.user_ops {
+            Some(user_ops) => {
+                user_ops.lock().unwrap_or_else(|e| e.into_inner()).push(msg);
+                None
+            }
+            None => Some(msg),
+        }
+    }
+
+    /// **The staged user ops, in the order they were handled**
+    pub fn take_user_ops(&self) -> Vec<UserOpMessage> {
+        self.user_ops
+            .as_ref()
+            .map(|user_ops| std::mem::take(&mut *user_ops.lock().unwrap_or_else(|e| e.into_inner())))
+            .unwrap_or_default()
+    }
+
+    fn complete(&self, stored: bool) {
+        if !stored {
+            self.failed.store(true, Ordering::SeqCst);
+        }
+        if self.remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // Keeps a permit when nobody is waiting yet
+            self.done.notify_one();
+        }
+    }
+
+    /// **Wait until every event was handled, `true` when all of them were stored**
+    ///
+    /// Gives up when the processor goes away, as the remaining events will never be handled.
+    pub async fn wait(&self, sender: &mpsc::Sender<Event>) -> bool {
+        if self.remaining.load(Ordering::SeqCst) > 0 {
+            tokio::select! {
+                _ = self.done.notified() => {}
+                _ = sender.closed() => return false,
+            }
+        }
+        !self.failed.load(Ordering::SeqCst)
+    }
+}
+
+/// **The bundle transaction that emitted a log, from its receipt and calldata**
+#[derive(Debug)]
+pub struct TransactionInfo {
+    pub bundler: Address,
+    pub effective_gas_price: u128,
+    pub bundle: Option<Bundle>, // None when the transaction is not a handleOps call
 }
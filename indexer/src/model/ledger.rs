@@ -0,0 +1,30 @@
+use sqlx::types::BigDecimal;
+
+/// Movement of a user's prepaid gas balance, booked against the paymaster holding it
+#[derive(Debug)]
+pub struct LedgerEntry {
+    pub chain_id: u32,
+    pub paymaster: String,
+    pub user: String,
+    pub amount: BigDecimal, // unsigned, the reason gives the direction
+    pub reason: LedgerReason,
+    pub block_number: u64,
+    pub tx_hash: String,
+    pub log_index: u64,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LedgerReason {
+    Deduction, // GasBalanceDeducted, the user pays the paymaster
+    Refund,    // RefundProcessed, the paymaster pays the user back
+}
+
+impl LedgerReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedgerReason::Deduction => "deduction",
+            LedgerReason::Refund => "refund",
+        }
+    }
+}
@@ -2,3 +2,11 @@ pub mod user_op_policy;
 pub mod paymaster_type;
 pub mod user_op;
 pub mod event;
+pub mod smart_account;
+pub mod revert_reason;
+pub mod paymaster_deposit;
+pub mod contract_event;
+pub mod sponsorship;
+pub mod ledger;
+pub mod dead_letter;
+pub mod spend;
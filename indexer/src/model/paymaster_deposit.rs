@@ -0,0 +1,23 @@
+use sqlx::types::BigDecimal;
+
+/// Change to a paymaster's EntryPoint deposit or stake, in the order it happened on chain
+#[derive(Debug)]
+pub struct DepositEvent {
+    pub chain_id: u32,
+    pub entry_point: String,
+    pub paymaster: String,
+    pub change: DepositChange,
+    pub block_number: u64,
+    pub log_index: u64,
+}
+
+#[derive(Debug)]
+pub enum DepositChange {
+    Deposited { total_deposit: BigDecimal },
+    Withdrawn { amount: BigDecimal },
+    /// Gas cost of a sponsored user op, taken from the deposit without an event of its own
+    GasCharged { amount: BigDecimal },
+    StakeLocked { total_staked: BigDecimal, unstake_delay_sec: u64 },
+    StakeUnlocked { withdraw_time: u64 },
+    StakeWithdrawn,
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
 
@@ -15,13 +17,14 @@ pub enum PaymasterMode {
     Unknown,
 }
 
-impl ToString for PaymasterMode {
-    fn to_string(&self) -> String {
-        match self {
-            PaymasterMode::SponsorshipPrepaid => "SPONSORSHIP_PREPAID".to_string(),
-            PaymasterMode::SponsorshipPostpaid => "SPONSORSHIP_POSTPAID".to_string(),
-            PaymasterMode::Token => "TOKEN".to_string(),
-            PaymasterMode::Unknown => "UNKNOWN".to_string(),
-        }
+impl fmt::Display for PaymasterMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            PaymasterMode::SponsorshipPrepaid => "SPONSORSHIP_PREPAID",
+            PaymasterMode::SponsorshipPostpaid => "SPONSORSHIP_POSTPAID",
+            PaymasterMode::Token => "TOKEN",
+            PaymasterMode::Unknown => "UNKNOWN",
+        };
+        f.write_str(mode)
     }
 }
\ No newline at end of file
@@ -0,0 +1,34 @@
+use serde::Serialize;
+
+/// Revert reason of a failed user op, from UserOperationRevertReason or PostOpRevertReason
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOpRevertReason {
+    pub chain_id: u32,
+    pub user_op_hash: String,
+    pub stage: RevertStage,
+    pub sender: String,
+    pub nonce: String,
+    pub reason_type: String, // Error, Panic, Custom or Unknown
+    pub reason: Option<String>,
+    pub selector: Option<String>,
+    pub revert_data: String, // raw revert bytes as hex
+    pub block_number: Option<u64>,
+    pub tx_hash: Option<String>,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum RevertStage {
+    Execution, // the account call reverted
+    PostOp,    // the paymaster postOp reverted
+}
+
+impl RevertStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevertStage::Execution => "execution",
+            RevertStage::PostOp => "postOp",
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use serde::Serialize;
+
+/// Account deployed by a sponsored user op, from the EntryPoint AccountDeployed event
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartAccount {
+    pub chain_id: u32,
+    pub sender: String,
+    pub factory: String,
+    pub user_op_hash: String,
+    pub paymaster: String,
+    pub block_number: u64,
+    pub timestamp: String,
+    pub tx_hash: Option<String>,
+}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::BigDecimal, FromRow};
+
+#[derive(Debug, Clone, Copy)]
+pub enum SpendInterval {
+    Hour,
+    Day,
+}
+
+impl SpendInterval {
+    /// **Continuous aggregate holding the buckets of this size**
+    pub fn view(&self) -> &'static str {
+        match self {
+            SpendInterval::Hour => "user_op_spend_hourly",
+            SpendInterval::Day => "user_op_spend_daily",
+        }
+    }
+}
+
+/// Spend buckets to read, filters left to None match everything
+#[derive(Debug)]
+pub struct SpendQuery {
+    pub interval: SpendInterval,
+    pub from: DateTime<Utc>, // inclusive bucket start
+    pub to: DateTime<Utc>,   // exclusive bucket start
+    pub chain_id: Option<u32>,
+    pub paymaster_mode: Option<String>,
+    pub paymaster_id: Option<String>,
+    pub org_id: Option<String>,
+    pub policy_id: Option<String>,
+}
+
+/// Mined ops and their cost over one bucket, for one chain, paymaster, org and policy
+#[derive(Debug, FromRow)]
+pub struct SpendBucket {
+    pub bucket: DateTime<Utc>,
+    pub chain_id: i32,
+    pub paymaster_mode: Option<String>,
+    pub paymaster_id: Option<String>,
+    pub org_id: Option<String>,
+    pub policy_id: Option<String>,
+    pub ops: i64,
+    pub failed_ops: i64,
+    pub gas_used: Option<BigDecimal>,
+    pub native_cost_wei: Option<BigDecimal>,
+    pub usd_amount: Option<BigDecimal>,
+}
@@ -0,0 +1,29 @@
+use serde::Serialize;
+use sqlx::types::BigDecimal;
+
+/// Prepaid sponsorship of a user op, from the paymaster UserOperationSponsored event
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOpSponsorship {
+    pub chain_id: u32,
+    pub user_op_hash: String,
+    pub user: String,
+    pub paymaster: String,
+    pub block_number: u64,
+    pub tx_hash: Option<String>,
+    pub log_index: u64,
+    pub timestamp: String,
+}
+
+/// Prepaid balance refund, from the paymaster RefundProcessed event
+#[derive(Debug)]
+pub struct GasRefund {
+    pub chain_id: u32,
+    pub paymaster: String,
+    pub user: String,
+    pub amount: BigDecimal,
+    pub block_number: u64,
+    pub tx_hash: String,
+    pub log_index: u64,
+    pub timestamp: String,
+}
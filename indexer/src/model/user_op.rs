@@ -1,6 +1,5 @@
 use derive_more::derive::Display;
 use serde::{Deserialize, Serialize};
-use sqlx::{types::BigDecimal, FromRow};
 
 use super::paymaster_type::PaymasterMode;
 
@@ -22,6 +21,9 @@ pub struct UserOpMessage {
     pub native_usd_price: Option<String>,
     pub user_op_hash: String,
     pub enabled_limits: Option<Vec<String>>,
+    pub block_number: Option<u64>,
+    pub block_hash: Option<String>,
+    pub entry_point_version: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Display)]
@@ -32,13 +34,6 @@ pub enum Status {
     #[serde(other)]
     Unknown,
 }
-#[derive(Debug, FromRow)]
-pub struct UserOperationRecord {
-    pub status: Option<String>,
-    pub native_usd_price: Option<BigDecimal>,
-    pub actual_gas_cost: Option<i64>,
-    pub usd_amount: Option<BigDecimal>,
-}
 
 impl Status {
     pub fn from_str_case_insensitive(s: &str) -> Self {
@@ -50,6 +45,7 @@ impl Status {
         }
     }
 
+    // Mirrored by user_op_status_priority() in SQL, which the upsert uses
     pub fn priority(&self) -> i32 {
         match self {
             Status::Failed => 3,
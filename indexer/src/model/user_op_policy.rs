@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct UserOpPolicyData {
@@ -8,4 +8,26 @@ pub struct UserOpPolicyData {
     pub actual_gas_used: Option<String>,
     pub sender: Option<String>,
     pub enabled_limits: Option<Vec<String>>,
-}
\ No newline at end of file
+}
+
+/// Usage that was added to the Redis limit counters for a user op, kept so it can be reverted on reorg
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppliedUsage {
+    pub policy_id: String,
+    pub sender: Option<String>,
+    pub enabled_limits: Vec<String>,
+    pub gas: u64,
+    #[serde(deserialize_with = "decimal_string")]
+    pub usd_spent: String, // exact decimal
+    #[serde(deserialize_with = "decimal_string")]
+    pub gas_cost_eth: String, // exact decimal
+}
+
+// Entries applied before amounts were exact hold JSON numbers
+fn decimal_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => Ok(s),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        other => Err(de::Error::custom(format!("expected a decimal, got {}", other))),
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use alloy::json_abi::Event as AbiEvent;
+use alloy::primitives::{Address, B256};
+use anyhow::Context;
+use async_trait::async_trait;
+
+use crate::{
+    app::AppContext, cache::Cache, config::config::Config, events::dynamic::{decode_event_args, parse_event_abi},
+    model::contract_event::ContractEvent, model::dead_letter::UnprocessableInput, model::event::Event, processor::handler::event_time,
+    processor::registry::EventHandler, storage::Storage,
+};
+
+/// **Stores the logs of events configured with a full ABI in contract_events**
+pub struct ContractEventHandler {
+    events: HashMap<(u32, Address, B256), AbiEvent>,
+}
+
+impl ContractEventHandler {
+    /// **Parse the ABI of every configured event that has one, panics on an invalid ABI**
+    pub fn new(config: &Config) -> Self {
+        let mut events = HashMap::new();
+        for chain in config.chains.values() {
+            for contract in &chain.contracts {
+                let Ok(addr) = Address::from_str(&contract.address) else {
+                    continue;
+                };
+                for event in &contract.events {
+                    let Some(abi) = event.abi.as_deref() else {
+                        continue;
+                    };
+                    let signature = B256::from_str(&event.signature).expect("Invalid event signature");
+                    let abi_event = parse_event_abi(abi, signature).unwrap_or_else(|e| {
+                        panic!("Invalid ABI for {} of {} on chain {}: {:?}", event.name, contract.name, chain.chain_id, e)
+                    });
+                    events.insert((chain.chain_id, addr, signature), abi_event);
+                }
+            }
+        }
+        Self { events }
+    }
+
+    /// **Signatures of the events this handler decodes**
+    pub fn signatures(&self) -> Vec<B256> {
+        let mut signatures: Vec<B256> = self.events.keys().map(|(_, _, signature)| *signature).collect();
+        signatures.sort();
+        signatures.dedup();
+        signatures
+    }
+}
+
+#[async_trait]
+impl<S, C> EventHandler<S, C> for ContractEventHandler
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    async fn handle(&self, _event_name: &str, event: &Event, app: &Arc<AppContext<S, C>>) -> anyhow::Result<()> {
+        let Some(signature) = event.log.topic0() else {
+            return Ok(());
+        };
+        // The same signature may have an ABI on another contract only
+        let Some(abi_event) = self.events.get(&(event.chain_id, event.log.address(), *signature)) else {
+            return Ok(());
+        };
+
+        let args = decode_event_args(abi_event, &event.log.inner.data)
+            .map_err(|e| UnprocessableInput(format!("{:?}", e)))?;
+        let (Some(block_number), Some(log_index)) = (event.log.block_number, event.log.log_index) else {
+            tracing::error!("❌ {} log has no block number or log index, skipping", abi_event.name);
+            return Ok(());
+        };
+
+        let contract_event = ContractEvent {
+            chain_id: event.chain_id,
+            contract_address: format!("{:?}", event.log.address()),
+            event_name: abi_event.name.clone(),
+            event_signature: format!("{:?}", signature),
+            block_number,
+            block_hash: event.log.block_hash.map(|h| format!("{:?}", h)),
+            tx_hash: event.log.transaction_hash.map(|h| format!("{:?}", h)),
+            log_index,
+            args,
+            timestamp: event_time(event).to_rfc3339(),
+        };
+        app.storage
+            .insert_contract_event(contract_event)
+            .await
+            .with_context(|| format!("Failed to insert {} into contract_events", abi_event.name))
+    }
+}
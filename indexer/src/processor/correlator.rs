@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use alloy::primitives::{Log as AlloyLog, B256};
+use alloy_sol_types::SolEvent;
+use crate::events::events::UserOperationSponsoredForPostpaid;
+
+use crate::{metrics, model::event::Event};
+
+// A bundle's logs are fetched in one block range, so a paymaster log still waiting after this long has no op
+const UNMATCHED_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// **A buffered paymaster log**
+pub struct PaymasterLog {
+    pub event_name: String,
+    pub event: Event,
+    received_at: Instant,
+}
+
+/// **Paymaster logs waiting for the UserOperationEvent of their op**
+#[derive(Default)]
+pub struct PaymasterLogs {
+    by_tx: HashMap<(u32, B256), Vec<PaymasterLog>>, // ordered by log index
+    by_user_op: HashMap<(u32, B256), Vec<PaymasterLog>>,
+}
+
+impl PaymasterLogs {
+    /// **Buffer a paymaster log, keyed by its userOpHash when it carries one, else by its transaction**
+    pub fn push(&mut self, event_name: &str, event: &Event) {
+        let pending = PaymasterLog {
+            event_name: event_name.to_string(),
+            event: event.clone(),
+            received_at: Instant::now(),
+        };
+
+        if event_name == "UserOperationSponsoredForPostpaid" {
+            if let Ok(log) = UserOperationSponsoredForPostpaid::decode_log(&AlloyLog::from(event.log.clone()), true) {
+                self.by_user_op.entry((event.chain_id, log.userOpHash)).or_default().push(pending);
+                return;
+            }
+        }
+
+        let Some(tx_hash) = event.log.transaction_hash else {
+            report_unmatched(&pending, "log has no transaction hash");
+            return;
+        };
+        let logs = self.by_tx.entry((event.chain_id, tx_hash)).or_default();
+        let position = logs.partition_point(|p| p.event.log.log_index <= event.log.log_index);
+        logs.insert(position, pending);
+    }
+
+    /// **Take the paymaster logs of the op a UserOperationEvent was emitted for**
+    ///
+    /// Logs carrying the userOpHash match on it. The others are emitted by postOp, after the
+    /// previous op's UserOperationEvent and before this one, so every UserOperationEvent of a
+    /// transaction must come through here, ours or not, to close its window.
+    pub fn take_for_user_op(&mut self, event: &Event, user_op_hash: B256) -> Vec<PaymasterLog> {
+        let mut matched = self.by_user_op.remove(&(event.chain_id, user_op_hash)).unwrap_or_default();
+
+        if let (Some(tx_hash), Some(log_index)) = (event.log.transaction_hash, event.log.log_index) {
+            let key = (event.chain_id, tx_hash);
+            if let Some(logs) = self.by_tx.get_mut(&key) {
+                let window = logs.partition_point(|p| p.event.log.log_index.is_some_and(|i| i < log_index));
+                matched.extend(logs.drain(..window));
+                if logs.is_empty() {
+                    self.by_tx.remove(&key);
+                }
+            }
+        }
+        matched
+    }
+
+    /// **Report logs taken by an op they cannot belong to**
+    pub fn reject(&self, logs: &[PaymasterLog], reason: &str) {
+        for log in logs {
+            report_unmatched(log, reason);
+        }
+    }
+
+    /// **Drop and report logs that found no UserOperationEvent in time**
+    pub fn expire(&mut self) {
+        for buffer in [&mut self.by_tx, &mut self.by_user_op] {
+            buffer.retain(|_, logs| {
+                logs.retain(|p| {
+                    let alive = p.received_at.elapsed() < UNMATCHED_TIMEOUT;
+                    if !alive {
+                        report_unmatched(p, "no UserOperationEvent within timeout");
+                    }
+                    alive
+                });
+                !logs.is_empty()
+            });
+        }
+    }
+}
+
+fn report_unmatched(pending: &PaymasterLog, reason: &str) {
+    let chain_id = pending.event.chain_id.to_string();
+    tracing::warn!(
+        event = "paymaster_log_unmatched",
+        chain_id = %chain_id,
+        log = %pending.event_name,
+        tx_hash = ?pending.event.log.transaction_hash,
+        log_index = ?pending.event.log.log_index,
+        reason,
+        "⚠️ {} log on chain {} was not paired with a UserOperationEvent: {}",
+        pending.event_name,
+        chain_id,
+        reason
+    );
+    metrics::inc_counter(
+        "indexer_unmatched_paymaster_logs_total",
+        "Paymaster logs that could not be paired with a UserOperationEvent",
+        &[("chain_id", &chain_id), ("event", &pending.event_name)],
+        1.0,
+    );
+}
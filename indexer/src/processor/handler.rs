@@ -1,154 +1,515 @@
-use std::{collections::{HashMap, HashSet}, sync::Arc};
+use std::{collections::{HashMap, HashSet}, str::FromStr, sync::{Arc, Mutex, MutexGuard}};
 
 use alloy_sol_types::SolEvent;
-use alloy::primitives::{Address, Log as AlloyLog};
-use chrono::Utc;
-use indexer::events::events::{
-    GasBalanceDeducted, RefundProcessed, UserOperationEvent, UserOperationSponsored, PaidGasInTokens, UserOperationSponsoredForPostpaid
+use anyhow::Context;
+use alloy::primitives::{Address, Log as AlloyLog, B256};
+use chrono::{DateTime, Utc};
+use sqlx::types::BigDecimal;
+use async_trait::async_trait;
+use crate::events::events::{
+    AccountDeployed, Deposited, GasBalanceDeducted, PostOpRevertReason, StakeLocked, StakeUnlocked, StakeWithdrawn, Withdrawn, RefundProcessed, UserOperationEvent, UserOperationRevertReason,
+    UserOperationSponsored, PaidGasInTokens, UserOperationSponsoredForPostpaid
 };
+use crate::events::revert_reason::RevertReason;
 use serde_json::json;
 use crate::{
-    app::AppContext, cache::Cache, model::user_op::{Status, UserOpMessage}, model::{paymaster_type::PaymasterMode, user_op_policy::UserOpPolicyData}, storage::Storage,
-    model::event::Event,
+    app::AppContext, cache::Cache, events::entry_point::EntryPointVersion, model::user_op::{Status, UserOpMessage}, model::{paymaster_type::PaymasterMode, user_op_policy::UserOpPolicyData}, storage::Storage,
+    model::event::Event, model::smart_account::SmartAccount, model::sponsorship::{GasRefund, UserOpSponsorship}, model::ledger::{LedgerEntry, LedgerReason}, model::dead_letter::UnprocessableInput, model::revert_reason::{RevertStage, UserOpRevertReason},
+    model::paymaster_deposit::{DepositChange, DepositEvent}, utils::u256_to_decimal,
+    processor::{correlator::PaymasterLogs, registry::EventHandler}, config::config::Config,
 };
 
-// **Process a log based on the event name**
-pub async fn process_event<S, C>(
-    event_name: &str,
-    event: &Event,
-    previous_event: &mut Option<Event>,
-    app: Arc<AppContext<S, C>>,
-    allowed_contracts: &HashMap<u32, HashSet<Address>>,
-)
-where
-    S: Storage + Send + Sync + 'static,
-    C: Cache + Send + Sync + 'static,
-{   
-    let alloy_log = AlloyLog::from(event.log.clone());
-    match event_name {
-        "GasBalanceDeducted" | "PaidGasInTokens" | "UserOperationSponsoredForPostpaid" => {
-            // Store this log to be paired with the next UserOperationEvent
-            *previous_event = Some(event.clone());
-        }
-        "UserOperationEvent" => {
-            let user_op_log = AlloyLog::from(event.log.clone());
-            if let Ok(log) = UserOperationEvent::decode_log(&user_op_log, false) {
-                // ⚠️ Filter only events involving our contracts
-                let chain_id = event.chain_id;
-                let paymaster = log.paymaster;
-                if let Some(allowed) = allowed_contracts.get(&chain_id) {
-                    if !allowed.contains(&paymaster) {
-                        tracing::warn!("⛔ Ignoring UserOperationEvent with disallowed paymaster: {:?} for chain {}", paymaster, chain_id);
-                        return;
+/// **Built-in handling of paymaster and EntryPoint events**
+///
+/// Paymaster logs and revert reasons are held until the UserOperationEvent of their op,
+/// the buffers are shared by all workers, which never touch the same transaction.
+pub struct UserOperationHandler {
+    allowed_contracts: HashMap<u32, HashSet<Address>>,
+    entry_points: HashMap<(u32, Address), EntryPointVersion>,
+    paymaster_logs: Mutex<PaymasterLogs>,
+    revert_reasons: Mutex<HashMap<B256, Vec<UserOpRevertReason>>>,
+}
+
+impl UserOperationHandler {
+    pub fn new(config: &Config) -> Self {
+        let mut allowed_contracts: HashMap<u32, HashSet<Address>> = HashMap::new();
+        let mut entry_points: HashMap<(u32, Address), EntryPointVersion> = HashMap::new();
+        for chain in config.chains.values() {
+            let chain_id = chain.chain_id;
+            for contract in &chain.contracts {
+                // Add contract address to allowed set
+                if let Ok(addr) = Address::from_str(&contract.address) {
+                    allowed_contracts.entry(chain_id).or_default().insert(addr);
+
+                    // EntryPoints configured before versions existed are v0.7
+                    let emits_user_ops = contract.events.iter().any(|e| e.name == "UserOperationEvent");
+                    match contract.entry_point_version {
+                        Some(version) => {
+                            entry_points.insert((chain_id, addr), version);
+                        }
+                        None if emits_user_ops => {
+                            tracing::warn!("⚠️ No entry_point_version for {} on chain {}, assuming 0.7", contract.name, chain_id);
+                            entry_points.insert((chain_id, addr), EntryPointVersion::V07);
+                        }
+                        None => {}
                     }
-                } else {
-                    tracing::warn!("⛔ No allowed contracts found for chain {}. Skipping.", chain_id);
-                    return;
                 }
-                // Prepare metadata
-                let mut meta = serde_json::Map::new();
-                let mut paymaster_type = PaymasterMode::Unknown;
-                let mut token_address: Option<String> = None;
-
-                if let Some(prev_event) = previous_event.take() {
-                    let prev_log = AlloyLog::from(prev_event.log);
-
-                    if let Ok(decoded) = GasBalanceDeducted::decode_log(&prev_log, true) {
-                        meta.insert("deductedUser".to_string(), json!(decoded.user.to_string()));
-                        meta.insert("deductedAmount".to_string(), json!(decoded.amount.to_string()));
-                        meta.insert("premium".to_string(), json!(decoded.premium.to_string()));
-                        paymaster_type = PaymasterMode::SponsorshipPrepaid;
+            }
+        }
+        Self {
+            allowed_contracts,
+            entry_points,
+            paymaster_logs: Mutex::new(PaymasterLogs::default()),
+            revert_reasons: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// **Signatures of the events handled here**
+    pub fn signatures() -> Vec<B256> {
+        vec![
+            GasBalanceDeducted::SIGNATURE_HASH,
+            PaidGasInTokens::SIGNATURE_HASH,
+            UserOperationSponsoredForPostpaid::SIGNATURE_HASH,
+            UserOperationSponsored::SIGNATURE_HASH,
+            RefundProcessed::SIGNATURE_HASH,
+            UserOperationEvent::SIGNATURE_HASH,
+            UserOperationRevertReason::SIGNATURE_HASH,
+            PostOpRevertReason::SIGNATURE_HASH,
+            AccountDeployed::SIGNATURE_HASH,
+            Deposited::SIGNATURE_HASH,
+            Withdrawn::SIGNATURE_HASH,
+            StakeLocked::SIGNATURE_HASH,
+            StakeUnlocked::SIGNATURE_HASH,
+            StakeWithdrawn::SIGNATURE_HASH,
+        ]
+    }
+
+    /// **Signatures of the logs kept until the UserOperationEvent of their op, which stores them**
+    pub fn paired_signatures() -> [B256; 5] {
+        [
+            GasBalanceDeducted::SIGNATURE_HASH,
+            PaidGasInTokens::SIGNATURE_HASH,
+            UserOperationSponsoredForPostpaid::SIGNATURE_HASH,
+            UserOperationRevertReason::SIGNATURE_HASH,
+            PostOpRevertReason::SIGNATURE_HASH,
+        ]
+    }
+
+    fn paymaster_logs(&self) -> MutexGuard<'_, PaymasterLogs> {
+        self.paymaster_logs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn revert_reasons(&self) -> MutexGuard<'_, HashMap<B256, Vec<UserOpRevertReason>>> {
+        self.revert_reasons.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // **Process a log based on the event name**
+    // Errors mean the event was not stored and its block must be indexed again, undecodable logs are dead-lettered
+    async fn process_event<S, C>(&self, event_name: &str, event: &Event, app: &Arc<AppContext<S, C>>) -> anyhow::Result<()>
+    where
+        S: Storage + Send + Sync + 'static,
+        C: Cache + Send + Sync + 'static,
+    {   
+        let alloy_log = AlloyLog::from(event.log.clone());
+        match event_name {
+            "GasBalanceDeducted" | "PaidGasInTokens" | "UserOperationSponsoredForPostpaid" => {
+                // Prepaid balances are booked right away, the entry is linked to its op with the refunds
+                if event_name == "GasBalanceDeducted" {
+                    if let Ok(log) = GasBalanceDeducted::decode_log(&alloy_log, true) {
+                        if let Some(entry) = ledger_entry(event, log.user, u256_to_decimal(log.amount), LedgerReason::Deduction) {
+                            app.storage.record_ledger_entry(entry).await.context("Failed to book gas deduction in the ledger")?;
+                        }
                     }
+                }
+                // Kept until the UserOperationEvent of its op, matched by userOpHash or log order
+                self.paymaster_logs().push(event_name, event);
+            }
+            "UserOperationEvent" => {
+                let user_op_log = AlloyLog::from(event.log.clone());
+                if let Ok(log) = UserOperationEvent::decode_log(&user_op_log, false) {
+                    // Revert reasons are emitted earlier in the same transaction
+                    let pending_reverts = self.revert_reasons().remove(&log.userOpHash).unwrap_or_default();
+                    let paired_logs = self.paymaster_logs().take_for_user_op(event, log.userOpHash);
 
-                    if let Ok(_) = UserOperationSponsoredForPostpaid::decode_log(&prev_log, true) {
-                        paymaster_type = PaymasterMode::SponsorshipPostpaid;
+                    // ⚠️ Filter only events involving our contracts
+                    let chain_id = event.chain_id;
+                    let paymaster = log.paymaster;
+                    if let Some(allowed) = self.allowed_contracts.get(&chain_id) {
+                        if !allowed.contains(&paymaster) {
+                            tracing::warn!("⛔ Ignoring UserOperationEvent with disallowed paymaster: {:?} for chain {}", paymaster, chain_id);
+                            self.paymaster_logs().reject(&paired_logs, "op was sponsored by another paymaster");
+                            return Ok(());
+                        }
+                    } else {
+                        tracing::warn!("⛔ No allowed contracts found for chain {}. Skipping.", chain_id);
+                        return Ok(());
                     }
+                    let entry_point_version = self.entry_points.get(&(chain_id, event.log.address())).copied();
+
+                    // Prepare metadata
+                    let mut meta = serde_json::Map::new();
+                    let mut paymaster_type = PaymasterMode::Unknown;
+                    let mut token_address: Option<String> = None;
+                    let mut prepaid_user: Option<Address> = None;
 
-                    if let Ok(decoded) = PaidGasInTokens::decode_log(&prev_log, true) {
-                        meta.insert("deductedUser".to_string(), json!(decoded.user.to_string()));
-                        meta.insert("token".to_string(), json!(decoded.token));
-                        meta.insert("tokenCharge".to_string(), json!(decoded.tokenCharge.to_string()));
-                        meta.insert("appliedMarkup".to_string(), json!(decoded.appliedMarkup.to_string()));
-                        meta.insert("exchangeRate".to_string(), json!(decoded.exchangeRate.to_string()));
-                        token_address = Some(format!("{:?}", decoded.token));
-                        paymaster_type = PaymasterMode::Token;
+                    if paired_logs.is_empty() {
+                        tracing::warn!("⚠️ UserOperationEvent {:?} has no matched paymaster log. Proceeding anyway.", log.userOpHash);
+                    }
+                    for paired in &paired_logs {
+                        let paired_log = AlloyLog::from(paired.event.log.clone());
+                        match paired.event_name.as_str() {
+                            "GasBalanceDeducted" => {
+                                if let Ok(decoded) = GasBalanceDeducted::decode_log(&paired_log, true) {
+                                    meta.insert("deductedUser".to_string(), json!(decoded.user.to_string()));
+                                    meta.insert("deductedAmount".to_string(), json!(decoded.amount.to_string()));
+                                    meta.insert("premium".to_string(), json!(decoded.premium.to_string()));
+                                    prepaid_user = Some(decoded.user);
+                                    paymaster_type = PaymasterMode::SponsorshipPrepaid;
+                                }
+                            }
+                            "UserOperationSponsoredForPostpaid" => {
+                                paymaster_type = PaymasterMode::SponsorshipPostpaid;
+                            }
+                            "PaidGasInTokens" => {
+                                if let Ok(decoded) = PaidGasInTokens::decode_log(&paired_log, true) {
+                                    meta.insert("deductedUser".to_string(), json!(decoded.user.to_string()));
+                                    meta.insert("token".to_string(), json!(decoded.token));
+                                    meta.insert("tokenCharge".to_string(), json!(decoded.tokenCharge.to_string()));
+                                    meta.insert("appliedMarkup".to_string(), json!(decoded.appliedMarkup.to_string()));
+                                    meta.insert("exchangeRate".to_string(), json!(decoded.exchangeRate.to_string()));
+                                    token_address = Some(format!("{:?}", decoded.token));
+                                    paymaster_type = PaymasterMode::Token;
+                                }
+                            }
+                            _ => {}
+                        }
                     }
-                } else {
-                    tracing::warn!("⚠️ UserOperationEvent has no matched previous log. Proceeding anyway.");
-                }
 
-                // Add gas cost/use to metadata
-                meta.insert("actualGasCost".to_string(), json!(log.actualGasCost.to_string()));
-                meta.insert("actualGasUsed".to_string(), json!(log.actualGasUsed.to_string()));
+                    // Add gas cost/use to metadata
+                    meta.insert("actualGasCost".to_string(), json!(log.actualGasCost.to_string()));
+                    meta.insert("actualGasUsed".to_string(), json!(log.actualGasUsed.to_string()));
 
-                let msg = UserOpMessage {
-                    org_id: None,
-                    credential_id: None,
-                    paymaster_mode: Some(paymaster_type.clone()),
-                    paymaster_id: None,
-                    token_address,
-                    chain_id: event.chain_id,
-                    policy_id: None,
-                    native_usd_price: None,
-                    enabled_limits: None,
-                    status: if log.success { Status::Success } else { Status::Failed },
-                    user_op_hash: format!("{:?}", log.userOpHash),
-                    data_source: Some("Indexer".to_string()),
-                    timestamp: Utc::now().to_rfc3339(),
-                    user_op: json!({
-                        "sender": format!("{:?}", log.sender),
-                        "paymaster": format!("{:?}", log.paymaster),
-                        "nonce": log.nonce.to_string(),
-                    }),
-                    meta_data: Some(json!(meta)),
-                };
+                    // Add the bundle transaction that included the op
+                    if let Some(tx_hash) = event.log.transaction_hash {
+                        meta.insert("transactionHash".to_string(), json!(format!("{:?}", tx_hash)));
+                    }
+                    if let Some(log_index) = event.log.log_index {
+                        meta.insert("logIndex".to_string(), json!(log_index.to_string()));
+                    }
+                    if let Some(tx) = event.transaction.as_ref() {
+                        meta.insert("bundler".to_string(), json!(format!("{:?}", tx.bundler)));
+                        meta.insert("effectiveGasPrice".to_string(), json!(tx.effective_gas_price.to_string()));
+                        if let Some(bundle) = tx.bundle.as_ref() {
+                            meta.insert("beneficiary".to_string(), json!(format!("{:?}", bundle.beneficiary)));
+                        }
+                    }
 
-                // Optional: only update Redis for policies if it's a prepaid/postpaid paymaster type
-                if matches!(paymaster_type, PaymasterMode::SponsorshipPrepaid | PaymasterMode::SponsorshipPostpaid) {
-                    let redis_payload = UserOpPolicyData {
+                    let msg = UserOpMessage {
+                        org_id: None,
+                        credential_id: None,
+                        paymaster_mode: Some(paymaster_type.clone()),
+                        paymaster_id: None,
+                        token_address,
+                        chain_id: event.chain_id,
                         policy_id: None,
                         native_usd_price: None,
-                        actual_gas_cost: Some(log.actualGasCost.to_string()),
-                        actual_gas_used: Some(log.actualGasUsed.to_string()),
-                        sender: None,
                         enabled_limits: None,
+                        status: if log.success { Status::Success } else { Status::Failed },
+                        user_op_hash: format!("{:?}", log.userOpHash),
+                        data_source: Some("Indexer".to_string()),
+                        timestamp: event_time(event).to_rfc3339(),
+                        user_op: entry_point_version
+                            .and_then(|version| decoded_user_op(event, log.userOpHash, version))
+                            .unwrap_or_else(|| json!({
+                            "sender": format!("{:?}", log.sender),
+                            "paymaster": format!("{:?}", log.paymaster),
+                            "nonce": log.nonce.to_string(),
+                        })),
+                        meta_data: Some(json!(meta)),
+                        block_number: event.log.block_number,
+                        block_hash: event.log.block_hash.map(|h| format!("{:?}", h)),
+                        entry_point_version: entry_point_version.map(|v| v.to_string()),
+                    };
+
+                    // Optional: only update Redis for policies if it's a prepaid/postpaid paymaster type
+                    if matches!(paymaster_type, PaymasterMode::SponsorshipPrepaid | PaymasterMode::SponsorshipPostpaid) {
+                        let redis_payload = UserOpPolicyData {
+                            policy_id: None,
+                            native_usd_price: None,
+                            actual_gas_cost: Some(log.actualGasCost.to_string()),
+                            actual_gas_used: Some(log.actualGasUsed.to_string()),
+                            sender: None,
+                            enabled_limits: None,
+                        };
+                        if let Err(e) = app.cache.update_userop_policy(&msg.user_op_hash, redis_payload).await {
+                            tracing::error!("❌ Failed to update Redis with indexer data: {:?}", e);
+                        }
+                    }
+
+                    // ✅ Store in Timescale
+                    tracing::info!("userOpMessage: {}", serde_json::to_string(&msg).unwrap());
+                    // Ops of an indexed block range are committed with its checkpoint, replayed ones right away
+                    let direct = match &event.ack {
+                        Some(ack) => ack.stage_user_op(msg),
+                        None => Some(msg),
                     };
-                    if let Err(e) = app.cache.update_userop_policy(&msg.user_op_hash, redis_payload).await {
-                        tracing::error!("❌ Failed to update Redis with indexer data: {:?}", e);
+                    if let Some(msg) = direct {
+                        app.storage.upsert_user_op_message(msg).await.context("Failed to upsert UserOpMessage into Timescale")?;
                     }
+                    for reason in pending_reverts {
+                        app.storage.upsert_revert_reason(reason).await.context("Failed to upsert revert reason into Timescale")?;
+                    }
+
+                    // The EntryPoint takes the gas cost from the paymaster deposit without a Withdrawn event
+                    if let Some(deposit_event) = deposit_event(event, log.paymaster, DepositChange::GasCharged {
+                        amount: u256_to_decimal(log.actualGasCost),
+                    }) {
+                        app.storage.apply_deposit_event(deposit_event).await.context("Failed to apply gas charge to paymaster deposit")?;
+                    }
+
+                    // Refunds of the prepaid balance are logged by the paymaster before the op's UserOperationEvent
+                    if let (Some(user), Some(tx_hash), Some(log_index)) = (prepaid_user, event.log.transaction_hash, event.log.log_index) {
+                        let user_op_hash = format!("{:?}", log.userOpHash);
+                        let linked = app.storage
+                            .link_to_user_op(chain_id, &format!("{:?}", tx_hash), log_index, &format!("{:?}", user), &user_op_hash)
+                            .await
+                            .context("Failed to link refunds and ledger entries to their user op")?;
+                        if linked > 0 {
+                            tracing::info!("💸 Linked {} refund(s) to UserOp {}", linked, user_op_hash);
+                        }
+                    }
+                } else {
+                    return Err(UnprocessableInput("failed to decode UserOperationEvent log".to_string()).into());
                 }
+            }
+            "UserOperationRevertReason" | "PostOpRevertReason" => {
+                let decoded = if event_name == "PostOpRevertReason" {
+                    PostOpRevertReason::decode_log(&alloy_log, true)
+                        .map(|log| (RevertStage::PostOp, log.userOpHash, log.sender, log.nonce, log.data.revertReason))
+                } else {
+                    UserOperationRevertReason::decode_log(&alloy_log, true)
+                        .map(|log| (RevertStage::Execution, log.userOpHash, log.sender, log.nonce, log.data.revertReason))
+                };
+                let Ok((stage, user_op_hash, sender, nonce, revert_data)) = decoded else {
+                    return Err(UnprocessableInput(format!("failed to decode {} log", event_name)).into());
+                };
 
-                // ✅ Store in Timescale
-                tracing::info!("userOpMessage: {}", serde_json::to_string(&msg).unwrap());
-                app.storage.upsert_user_op_message(msg).await.unwrap_or_else(|e| {
-                    tracing::error!("❌ Failed to upsert UserOpMessage into Timescale: {:?}", e);
+                let reason = RevertReason::decode(&revert_data);
+                tracing::info!("🧾 UserOp {:?} reverted in {}: {:?}", user_op_hash, stage.as_str(), reason);
+                // Kept until the UserOperationEvent of the op, which decides whether it is ours
+                self.revert_reasons().entry(user_op_hash).or_default().push(UserOpRevertReason {
+                    chain_id: event.chain_id,
+                    user_op_hash: format!("{:?}", user_op_hash),
+                    stage,
+                    sender: format!("{:?}", sender),
+                    nonce: nonce.to_string(),
+                    reason_type: reason.kind().to_string(),
+                    reason: reason.message(),
+                    selector: reason.selector(),
+                    revert_data: revert_data.to_string(),
+                    block_number: event.log.block_number,
+                    tx_hash: event.log.transaction_hash.map(|h| format!("{:?}", h)),
+                    timestamp: event_time(event).to_rfc3339(),
                 });
-            } else {
-                tracing::error!("❌ Failed to decode UserOperationEvent log");
             }
-        }
-        "UserOperationSponsored" => {
-            if let Ok(event) = UserOperationSponsored::decode_log(&alloy_log, true) {
-                tracing::info!(
-                    "✅ UserOperation Sponsored:\n\
-                    - userOpHash: {:?}\n\
-                    - user: {:?}",
-                    event.userOpHash, event.user
-                );
+            "Deposited" | "Withdrawn" | "StakeLocked" | "StakeUnlocked" | "StakeWithdrawn" => {
+                let decoded = match event_name {
+                    "Deposited" => Deposited::decode_log(&alloy_log, true).map(|log| {
+                        (log.account, DepositChange::Deposited { total_deposit: u256_to_decimal(log.totalDeposit) })
+                    }),
+                    "Withdrawn" => Withdrawn::decode_log(&alloy_log, true)
+                        .map(|log| (log.account, DepositChange::Withdrawn { amount: u256_to_decimal(log.amount) })),
+                    "StakeLocked" => StakeLocked::decode_log(&alloy_log, true).map(|log| {
+                        (log.account, DepositChange::StakeLocked {
+                            total_staked: u256_to_decimal(log.totalStaked),
+                            unstake_delay_sec: u64::try_from(log.unstakeDelaySec).unwrap_or(u64::MAX),
+                        })
+                    }),
+                    "StakeUnlocked" => StakeUnlocked::decode_log(&alloy_log, true).map(|log| {
+                        (log.account, DepositChange::StakeUnlocked {
+                            withdraw_time: u64::try_from(log.withdrawTime).unwrap_or(u64::MAX),
+                        })
+                    }),
+                    _ => StakeWithdrawn::decode_log(&alloy_log, true).map(|log| (log.account, DepositChange::StakeWithdrawn)),
+                };
+                let Ok((account, change)) = decoded else {
+                    return Err(UnprocessableInput(format!("failed to decode {} log", event_name)).into());
+                };
+
+                // ⚠️ Only deposits of our paymasters are tracked
+                let is_our_paymaster = self.allowed_contracts
+                    .get(&event.chain_id)
+                    .is_some_and(|allowed| allowed.contains(&account))
+                    && !self.entry_points.contains_key(&(event.chain_id, account));
+                if !is_our_paymaster {
+                    return Ok(());
+                }
+                if let Some(deposit_event) = deposit_event(event, account, change) {
+                    app.storage
+                        .apply_deposit_event(deposit_event)
+                        .await
+                        .with_context(|| format!("Failed to apply {} to paymaster deposit", event_name))?;
+                }
             }
-        }
-        "RefundProcessed" => {
-            if let Ok(event) = RefundProcessed::decode_log(&alloy_log, true) {
-                tracing::info!(
-                    "✅ Decoded RefundProcessed:\n\
-                    - user: {:?}\n\
-                    - amount: {}",
-                    event.user, event.amount
-                );
+            "AccountDeployed" => {
+                if let Ok(log) = AccountDeployed::decode_log(&alloy_log, true) {
+                    // ⚠️ Only accounts deployed through our paymasters are registered
+                    let sponsored = self.allowed_contracts
+                        .get(&event.chain_id)
+                        .is_some_and(|allowed| allowed.contains(&log.paymaster));
+                    if !sponsored {
+                        tracing::debug!("⛔ Ignoring AccountDeployed for {:?}, paymaster {:?} is not ours", log.sender, log.paymaster);
+                        return Ok(());
+                    }
+                    let Some(block_number) = event.log.block_number else {
+                        tracing::error!("❌ AccountDeployed log for {:?} has no block number", log.sender);
+                        return Ok(());
+                    };
+
+                    let account = SmartAccount {
+                        chain_id: event.chain_id,
+                        sender: format!("{:?}", log.sender),
+                        factory: format!("{:?}", log.factory),
+                        user_op_hash: format!("{:?}", log.userOpHash),
+                        paymaster: format!("{:?}", log.paymaster),
+                        block_number,
+                        timestamp: event_time(event).to_rfc3339(),
+                        tx_hash: event.log.transaction_hash.map(|h| format!("{:?}", h)),
+                    };
+                    app.storage.upsert_smart_account(account).await.context("Failed to upsert smart account into Timescale")?;
+                } else {
+                    return Err(UnprocessableInput("failed to decode AccountDeployed log".to_string()).into());
+                }
+            }
+            "UserOperationSponsored" => {
+                let Ok(log) = UserOperationSponsored::decode_log(&alloy_log, true) else {
+                    return Err(UnprocessableInput("failed to decode UserOperationSponsored log".to_string()).into());
+                };
+                let (Some(block_number), Some(log_index)) = (event.log.block_number, event.log.log_index) else {
+                    tracing::error!("❌ UserOperationSponsored log for {:?} has no block number or log index", log.userOpHash);
+                    return Ok(());
+                };
+                tracing::info!("✅ UserOperation {:?} sponsored for {:?}", log.userOpHash, log.user);
+
+                let sponsorship = UserOpSponsorship {
+                    chain_id: event.chain_id,
+                    user_op_hash: format!("{:?}", log.userOpHash),
+                    user: format!("{:?}", log.user),
+                    paymaster: format!("{:?}", event.log.address()),
+                    block_number,
+                    tx_hash: event.log.transaction_hash.map(|h| format!("{:?}", h)),
+                    log_index,
+                    timestamp: event_time(event).to_rfc3339(),
+                };
+                app.storage.upsert_sponsorship(sponsorship).await.context("Failed to upsert sponsorship into Timescale")?;
             }
+            "RefundProcessed" => {
+                let Ok(log) = RefundProcessed::decode_log(&alloy_log, true) else {
+                    return Err(UnprocessableInput("failed to decode RefundProcessed log".to_string()).into());
+                };
+                let (Some(block_number), Some(tx_hash), Some(log_index)) =
+                    (event.log.block_number, event.log.transaction_hash, event.log.log_index)
+                else {
+                    tracing::error!("❌ RefundProcessed log for {:?} has no block number, tx hash or log index", log.user);
+                    return Ok(());
+                };
+                tracing::info!("✅ Refund of {} to {:?}", log.amount, log.user);
+
+                // Linked to its user op by the UserOperationEvent that follows, if it was refunded in a bundle
+                let refund = GasRefund {
+                    chain_id: event.chain_id,
+                    paymaster: format!("{:?}", event.log.address()),
+                    user: format!("{:?}", log.user),
+                    amount: u256_to_decimal(log.amount),
+                    block_number,
+                    tx_hash: format!("{:?}", tx_hash),
+                    log_index,
+                    timestamp: event_time(event).to_rfc3339(),
+                };
+                app.storage.insert_refund(refund).await.context("Failed to insert refund into Timescale")?;
+                if let Some(entry) = ledger_entry(event, log.user, u256_to_decimal(log.amount), LedgerReason::Refund) {
+                    app.storage.record_ledger_entry(entry).await.context("Failed to book refund in the ledger")?;
+                }
+            }
+            _ => {
+                tracing::warn!("⚠️ Unrecognized event: {:?}", event_name);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S, C> EventHandler<S, C> for UserOperationHandler
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    async fn handle(&self, event_name: &str, event: &Event, app: &Arc<AppContext<S, C>>) -> anyhow::Result<()> {
+        self.process_event(event_name, event, app).await
+    }
+
+    fn tick(&self) {
+        self.paymaster_logs().expire();
+    }
+}
+
+/// **Full user op from the bundle calldata, matched by its userOpHash**
+fn decoded_user_op(event: &Event, user_op_hash: B256, version: EntryPointVersion) -> Option<serde_json::Value> {
+    let bundle = event.transaction.as_ref()?.bundle.as_ref()?;
+    match bundle.find_user_op(user_op_hash, version, event.log.address(), event.chain_id as u64) {
+        Some(user_op) => Some(user_op.to_json()),
+        None => {
+            tracing::warn!("⚠️ UserOp {:?} not found in its bundle calldata, storing event fields only", user_op_hash);
+            None
         }
-        _ => {
-            tracing::warn!("⚠️ Unrecognized event: {:?}", event_name);
+    }
+}
+
+/// **Deposit change of `paymaster` held by the EntryPoint that emitted the event**
+fn deposit_event(event: &Event, paymaster: Address, change: DepositChange) -> Option<DepositEvent> {
+    let (Some(block_number), Some(log_index)) = (event.log.block_number, event.log.log_index) else {
+        tracing::error!("❌ Deposit change of {:?} has no block number or log index, skipping", paymaster);
+        return None;
+    };
+    Some(DepositEvent {
+        chain_id: event.chain_id,
+        entry_point: format!("{:?}", event.log.address()),
+        paymaster: format!("{:?}", paymaster),
+        change,
+        block_number,
+        log_index,
+    })
+}
+
+/// **Prepaid balance movement of `user` at the paymaster that emitted the event**
+fn ledger_entry(event: &Event, user: Address, amount: BigDecimal, reason: LedgerReason) -> Option<LedgerEntry> {
+    let (Some(block_number), Some(tx_hash), Some(log_index)) =
+        (event.log.block_number, event.log.transaction_hash, event.log.log_index)
+    else {
+        tracing::error!("❌ Prepaid {} of {:?} has no block number, tx hash or log index, skipping", reason.as_str(), user);
+        return None;
+    };
+    Some(LedgerEntry {
+        chain_id: event.chain_id,
+        paymaster: format!("{:?}", event.log.address()),
+        user: format!("{:?}", user),
+        amount,
+        reason,
+        block_number,
+        tx_hash: format!("{:?}", tx_hash),
+        log_index,
+        timestamp: event_time(event).to_rfc3339(),
+    })
+}
+
+/// **Block time of the event, falling back to now when the node gave none**
+pub(crate) fn event_time(event: &Event) -> DateTime<Utc> {
+    match event.block_timestamp.and_then(|ts| DateTime::from_timestamp(ts as i64, 0)) {
+        Some(time) => time,
+        None => {
+            tracing::warn!("⚠️ No block timestamp for chain {} log, using current time", event.chain_id);
+            Utc::now()
         }
     }
 }
@@ -1,2 +1,7 @@
+#[allow(clippy::module_inception)]
 pub mod processor;
-pub mod handler;
\ No newline at end of file
+pub mod handler;
+pub mod correlator;
+pub mod pipeline;
+pub mod registry;
+pub mod contract_events;
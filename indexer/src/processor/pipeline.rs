@@ -0,0 +1,129 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+
+use crate::{
+    cache::Cache, config::config::ChainConfig, metrics, model::event::Event, processor::processor::ProcessEvent,
+    storage::Storage,
+};
+
+/// **Bounded queues and workers processing the events of one chain**
+///
+/// The listener sends to an ingress queue, a dispatcher hands each event to a worker picked by
+/// its transaction hash. The logs of one bundle are always handled by the same worker, in order,
+/// which is what pairing paymaster logs and revert reasons with their UserOperationEvent needs.
+pub struct ChainPipeline {
+    pub sender: mpsc::Sender<Event>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl ChainPipeline {
+    pub fn spawn<S, C>(chain: &ChainConfig, processor: Arc<ProcessEvent<S, C>>) -> Self
+    where
+        S: Storage + Send + Sync + 'static,
+        C: Cache + Send + Sync + 'static,
+    {
+        let chain_id = chain.chain_id.to_string();
+        let queue_size = chain.processor_queue_size.max(1);
+        let worker_count = chain.processor_workers.max(1);
+
+        let mut tasks = Vec::with_capacity(worker_count + 1);
+        let mut workers = Vec::with_capacity(worker_count);
+        for _ in 0..worker_count {
+            let (worker_sender, worker_receiver) = mpsc::channel(queue_size);
+            let processor = Arc::clone(&processor);
+            tasks.push(tokio::spawn(async move {
+                processor.process(worker_receiver).await;
+            }));
+            workers.push(worker_sender);
+        }
+
+        let (sender, receiver) = mpsc::channel(queue_size);
+        tasks.push(tokio::spawn(dispatch(chain_id, receiver, workers)));
+        tracing::info!(
+            "🧵 Chain {} processing with {} worker(s), queue size {}",
+            chain.chain_id,
+            worker_count,
+            queue_size
+        );
+
+        Self { sender, tasks }
+    }
+
+    /// **Close the ingress queue and wait until every queued event was processed**
+    pub async fn join(self) {
+        drop(self.sender);
+        for task in self.tasks {
+            if let Err(e) = task.await {
+                tracing::error!("🔥 Processor task failed: {:?}", e);
+            }
+        }
+    }
+}
+
+async fn dispatch(chain_id: String, mut receiver: mpsc::Receiver<Event>, workers: Vec<mpsc::Sender<Event>>) {
+    let worker_labels: Vec<String> = (0..workers.len()).map(|i| format!("worker-{}", i)).collect();
+
+    while let Some(event) = receiver.recv().await {
+        report_depth(&chain_id, "ingress", receiver.len(), receiver.max_capacity());
+
+        let partition = partition(&event, workers.len());
+        let worker = &workers[partition];
+        let label = worker_labels[partition].as_str();
+
+        // A full worker queue holds up the dispatcher, and through the ingress queue the listener
+        let sent = match worker.try_send(event) {
+            Ok(()) => true,
+            Err(TrySendError::Full(event)) => {
+                let blocked_since = Instant::now();
+                let labels = [("chain_id", chain_id.as_str()), ("queue", label)];
+                metrics::inc_counter(
+                    "indexer_processor_queue_full_total",
+                    "Events that found their processor queue full",
+                    &labels,
+                    1.0,
+                );
+                let sent = worker.send(event).await.is_ok();
+                metrics::inc_counter(
+                    "indexer_processor_blocked_seconds_total",
+                    "Time spent waiting for room in a full processor queue",
+                    &labels,
+                    blocked_since.elapsed().as_secs_f64(),
+                );
+                sent
+            }
+            Err(TrySendError::Closed(_)) => false,
+        };
+        if !sent {
+            // Dropping the ingress queue makes the listener give up on its pending range
+            tracing::error!("❌ Processor {} of chain {} stopped, closing the chain queue", label, chain_id);
+            return;
+        }
+        report_depth(&chain_id, label, worker.max_capacity() - worker.capacity(), worker.max_capacity());
+    }
+}
+
+/// **Worker of an event, the same for every log of a transaction**
+fn partition(event: &Event, workers: usize) -> usize {
+    if workers == 1 {
+        return 0;
+    }
+    let key = match event.log.transaction_hash {
+        Some(tx_hash) => u64::from_be_bytes(tx_hash[..8].try_into().expect("8 byte slice")),
+        None => event.log.block_number.unwrap_or_default(),
+    };
+    (key % workers as u64) as usize
+}
+
+fn report_depth(chain_id: &str, queue: &str, depth: usize, capacity: usize) {
+    let labels = [("chain_id", chain_id), ("queue", queue)];
+    metrics::set_gauge("indexer_processor_queue_depth", "Events waiting in a processor queue", &labels, depth as f64);
+    metrics::set_gauge(
+        "indexer_processor_queue_capacity",
+        "Size of a processor queue",
+        &labels,
+        capacity as f64,
+    );
+}
@@ -1,30 +1,33 @@
-
 use tokio::sync::mpsc;
-use alloy::primitives::{Address, B256};
+use alloy::primitives::B256;
 
-use std::collections::{HashSet, HashMap};
-use std::str::FromStr;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use alloy::hex;
+use anyhow::Context;
 use crate::app::AppContext;
 use crate::{
-    config::config::Config, 
-    processor::handler::process_event
+    config::config::Config,
+    processor::{contract_events::ContractEventHandler, handler::UserOperationHandler, registry::{EventHandler, HandlerRegistry}},
 };
 use crate::{
     storage::Storage,
     cache::Cache,
     model::event::Event,
+    model::dead_letter::{DeadLetter, DeadLetterSource, DeadLetterStage, UnprocessableInput},
 };
 
-pub struct ProcessEvent<S, C> 
+pub struct ProcessEvent<S, C>
 where
     S: Storage + Send + Sync + 'static,
     C: Cache + Send + Sync + 'static,
 {
     event_map: HashMap<B256, (String, Vec<String>)>,
     app: Arc<AppContext<S, C>>,
-    allowed_contracts: HashMap<u32, HashSet<Address>>,
+    handlers: HandlerRegistry<S, C>,
+    dead_letter_max_attempts: i32,
+    failed: Mutex<HashSet<String>>, // dead-letter keys of logs that failed, resolved once they succeed
 }
 
 impl<S, C> ProcessEvent<S, C>
@@ -32,13 +35,11 @@ where
     S: Storage + Send + Sync + 'static,
     C: Cache + Send + Sync + 'static,
 {
-    // **Initialize Processor with Dynamic Event Mapping**
+    // **Initialize Processor with Dynamic Event Mapping and the built-in handlers**
     pub fn new(config: &Config, app:Arc<AppContext<S, C>>) -> Self {
         let mut event_map = HashMap::new();
-        let mut allowed_contracts: HashMap<u32, HashSet<Address>> = HashMap::new();
         // 🔹 Iterate over all chains & their contracts
-        for (_, chain) in &config.chains {
-            let chain_id = chain.chain_id;
+        for chain in config.chains.values() {
             for contract in &chain.contracts {
                 for event in &contract.events {
                     let event_sig = B256::from_slice(
@@ -46,31 +47,136 @@ where
                     );
                     event_map.insert(event_sig, (event.name.clone(), event.params.clone()));
                 }
-
-                // Add contract address to allowed set
-                if let Ok(addr) = Address::from_str(&contract.address) {
-                    allowed_contracts.entry(chain_id).or_default().insert(addr);
-                }
             }
         }
-        Self { event_map, app, allowed_contracts }
+
+        let mut processor = Self {
+            event_map,
+            app,
+            handlers: HandlerRegistry::default(),
+            dead_letter_max_attempts: config.general.dead_letter_max_attempts,
+            failed: Mutex::new(HashSet::new()),
+        };
+
+        // Events with a full ABI are stored in contract_events first, built-in handling follows
+        let contract_events = ContractEventHandler::new(config);
+        let signatures = contract_events.signatures();
+        let contract_events: Arc<dyn EventHandler<S, C>> = Arc::new(contract_events);
+        for signature in signatures {
+            processor.register_handler(signature, Arc::clone(&contract_events));
+        }
+        let user_ops: Arc<dyn EventHandler<S, C>> = Arc::new(UserOperationHandler::new(config));
+        for signature in UserOperationHandler::signatures() {
+            processor.register_handler(signature, Arc::clone(&user_ops));
+        }
+        processor
+    }
+
+    /// **Add a handler for an event signature, after the ones already registered for it**
+    ///
+    /// Only logs of events listed in config.toml are fetched, so the event must be configured too.
+    pub fn register_handler(&mut self, signature: B256, handler: Arc<dyn EventHandler<S, C>>) {
+        self.handlers.register(signature, handler);
     }
 
     // **Process Incoming Logs Dynamically**
     pub async fn process(&self, mut receiver: mpsc::Receiver<Event>) {
-        let mut previous_event: Option<Event> = None;
+        // Lets handlers report logs they buffered for too long, e.g. unpaired paymaster logs
+        let mut ticks = tokio::time::interval(Duration::from_secs(30));
+
+        loop {
+            let event = tokio::select! {
+                event = receiver.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+                _ = ticks.tick() => {
+                    self.handlers.tick();
+                    continue;
+                }
+            };
+            let stored = match self.handle(&event).await {
+                Ok(()) => {
+                    self.resolve_if_failed(&event).await;
+                    true
+                }
+                Err(e) => self.dead_letter(&event, e).await,
+            };
+            event.complete(stored);
+        }
+    }
 
-        while let Some(event) = receiver.recv().await {
-            if let Some(event_signature) = event.log.topics().first() {
-                if let Some((event_name, _params)) = self.event_map.get(event_signature) {
-                    tracing::info!("✅ Processing Event: {}", event_name);
-                    process_event(event_name, &event, &mut previous_event, Arc::clone(&self.app), &self.allowed_contracts).await;
+    /// **Run the handlers of one log, stopping at the first error**
+    ///
+    /// Used by live indexing and by `indexer replay`, logs of events missing from config.toml are skipped.
+    pub async fn handle(&self, event: &Event) -> anyhow::Result<()> {
+        let Some(event_signature) = event.log.topics().first() else {
+            tracing::info!("⚠️ Log has no topics.");
+            return Ok(());
+        };
+        let Some((event_name, _params)) = self.event_map.get(event_signature) else {
+            tracing::info!("⚠️ Unknown event signature: {:?}", event_signature);
+            return Ok(());
+        };
+        let handlers = self.handlers.get(event_signature);
+        if handlers.is_empty() {
+            tracing::warn!("⚠️ Unrecognized event: {:?}", event_name);
+            return Ok(());
+        }
+
+        tracing::info!("✅ Processing Event: {}", event_name);
+        for handler in handlers {
+            handler
+                .handle(event_name, event, &self.app)
+                .await
+                .with_context(|| format!("Failed to process {} on chain {}", event_name, event.chain_id))?;
+        }
+        Ok(())
+    }
+
+    /// **Write a failed log to the dead-letter table, true when its block range may move on**
+    ///
+    /// Undecodable logs are skipped right away, storage failures are retried with the block range
+    /// until they failed `dead_letter_max_attempts` times.
+    async fn dead_letter(&self, event: &Event, error: anyhow::Error) -> bool {
+        tracing::error!("❌ {:?}", error);
+        let unprocessable = error.downcast_ref::<UnprocessableInput>().is_some();
+        let stage = if unprocessable { DeadLetterStage::Decode } else { DeadLetterStage::Store };
+
+        let letter = match DeadLetter::from_event(event, stage, &error) {
+            Ok(letter) => letter,
+            Err(e) => {
+                tracing::error!("❌ Failed to serialize dead letter: {:?}", e);
+                return false;
+            }
+        };
+        let key = letter.entry_key.clone();
+        match self.app.storage.record_dead_letter(letter).await {
+            Ok(attempts) => {
+                let skip = unprocessable || attempts >= self.dead_letter_max_attempts;
+                if skip {
+                    tracing::warn!("📮 Dead-lettered log {} after {} attempt(s), skipping it", key, attempts);
+                    self.failed.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
                 } else {
-                    tracing::info!("⚠️ Unknown event signature: {:?}", event_signature);
+                    self.failed.lock().unwrap_or_else(|e| e.into_inner()).insert(key);
                 }
-            } else {
-                tracing::info!("⚠️ Log has no topics.");
+                skip
+            }
+            Err(e) => {
+                tracing::error!("❌ Failed to record dead letter {}: {:?}", key, e);
+                false
+            }
+        }
+    }
+
+    /// **Resolve the dead letter of a log that failed before and went through on a retry**
+    async fn resolve_if_failed(&self, event: &Event) {
+        let key = DeadLetter::entry_key(event);
+        let failed = self.failed.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+        if failed {
+            if let Err(e) = self.app.storage.resolve_dead_letter_key(DeadLetterSource::Log, &key).await {
+                tracing::error!("❌ Failed to resolve dead letter {}: {:?}", key, e);
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use alloy::primitives::B256;
+use async_trait::async_trait;
+
+use crate::{app::AppContext, cache::Cache, model::event::Event, storage::Storage};
+
+/// **Handles the logs of the event signatures it is registered for**
+///
+/// One handler instance is shared by the workers of every chain. The logs of a transaction reach
+/// it in order from a single worker, logs of different transactions may be handled concurrently.
+#[async_trait]
+pub trait EventHandler<S, C>: Send + Sync
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    /// **Handle one log, `event_name` being its name in config.toml**
+    ///
+    /// An error means the log was not stored and its block range is indexed again, until it is
+    /// dead-lettered. Logs that can never be handled should fail with `UnprocessableInput`,
+    /// which dead-letters them right away; logs that are simply not ours return `Ok`.
+    async fn handle(&self, event_name: &str, event: &Event, app: &Arc<AppContext<S, C>>) -> anyhow::Result<()>;
+
+    /// **Called periodically by every worker, for handlers that buffer logs**
+    fn tick(&self) {}
+}
+
+/// **Event handlers by event signature, run in registration order**
+pub struct HandlerRegistry<S, C>
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    by_signature: HashMap<B256, Vec<Arc<dyn EventHandler<S, C>>>>,
+    handlers: Vec<Arc<dyn EventHandler<S, C>>>, // each registered handler once, for ticks
+}
+
+impl<S, C> Default for HandlerRegistry<S, C>
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self { by_signature: HashMap::new(), handlers: Vec::new() }
+    }
+}
+
+impl<S, C> HandlerRegistry<S, C>
+where
+    S: Storage + Send + Sync + 'static,
+    C: Cache + Send + Sync + 'static,
+{
+    pub fn register(&mut self, signature: B256, handler: Arc<dyn EventHandler<S, C>>) {
+        let known = self
+            .handlers
+            .iter()
+            .any(|h| std::ptr::addr_eq(Arc::as_ptr(h), Arc::as_ptr(&handler)));
+        if !known {
+            self.handlers.push(Arc::clone(&handler));
+        }
+        self.by_signature.entry(signature).or_default().push(handler);
+    }
+
+    pub fn get(&self, signature: &B256) -> &[Arc<dyn EventHandler<S, C>>] {
+        self.by_signature.get(signature).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn tick(&self) {
+        for handler in &self.handlers {
+            handler.tick();
+        }
+    }
+}
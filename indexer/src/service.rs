@@ -0,0 +1,358 @@
+use alloy_sol_types::SolEvent;
+use futures_util::FutureExt;
+use sqlx::migrate::Migrator;
+use std::collections::{hash_map::Entry, HashMap};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use alloy::primitives::B256;
+use alloy::rpc::types::Log;
+use anyhow::Context;
+use tokio::time::{sleep, Duration};
+
+use crate::app::AppContext;
+use crate::cache::redis::RedisCoordinator;
+use crate::config::config::{ChainConfig, Config};
+use crate::consumer::kafka_consumer::{handle_message, start_kafka_consumer};
+use crate::events::events::UserOperationEvent;
+use crate::listener::{backfill::BackfillJob, head_watcher::HeadWatcher, listener::EventListener};
+use crate::metrics;
+use crate::model::dead_letter::{DeadLetterRecord, DeadLetterSource, StoredLog};
+use crate::model::event::{Event, TransactionInfo};
+use crate::processor::{handler::UserOperationHandler, pipeline::ChainPipeline, processor::ProcessEvent, registry::EventHandler};
+use crate::storage::{time_scale::TimescaleStorage, Storage};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+pub type IndexerApp = AppContext<TimescaleStorage, RedisCoordinator>;
+
+fn spawn_safe<F>(fut: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        if let Err(err) = AssertUnwindSafe(fut).catch_unwind().await {
+            tracing::error!("🚨 A task panicked: {:?}", err);
+        }
+    });
+}
+
+/// **The indexer as a library: storage, configured chains and event handlers**
+///
+/// Services embedding it add their own handlers before starting it:
+///
+/// ```ignore
+/// let mut indexer = Indexer::new(Config::load()).await?;
+/// indexer.register_handler(Transfer::SIGNATURE_HASH, Arc::new(MyTransferHandler));
+/// indexer.run().await;
+/// ```
+pub struct Indexer {
+    config: Config,
+    app: Arc<IndexerApp>,
+    processor: ProcessEvent<TimescaleStorage, RedisCoordinator>,
+}
+
+impl Indexer {
+    /// **Connect to Timescale and Redis, migrate the database and register the built-in handlers**
+    pub async fn new(config: Config) -> anyhow::Result<Self> {
+        // ✅ Initialize DB and Redis
+        let db = Arc::new(TimescaleStorage::new(&config.storage).await);
+        let redis = Arc::new(RedisCoordinator::new(&config.storage.redis_url));
+
+        // ✅ DB migration
+        MIGRATOR.run(db.get_pg_pool()).await.context("DB migration failed")?;
+
+        // ✅ Wrap both into shared AppContext
+        let app: Arc<IndexerApp> = Arc::new(AppContext::new(db, redis));
+        let processor = ProcessEvent::new(&config, Arc::clone(&app));
+        Ok(Self { config, app, processor })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// **Storage and cache shared with the handlers**
+    pub fn app(&self) -> Arc<IndexerApp> {
+        Arc::clone(&self.app)
+    }
+
+    /// **Handle the logs of an event configured in config.toml, after the built-in handlers**
+    pub fn register_handler(
+        &mut self,
+        signature: B256,
+        handler: Arc<dyn EventHandler<TimescaleStorage, RedisCoordinator>>,
+    ) -> &mut Self {
+        self.processor.register_handler(signature, handler);
+        self
+    }
+
+    /// **Live indexing of every active chain plus the Kafka consumer, never returns**
+    pub async fn run(self) {
+        let Indexer { config, app, processor } = self;
+        let indexer_app = Arc::clone(&app);
+        let event_processor = Arc::new(processor);
+
+        // ✅ Expose metrics
+        if let Some(metrics_port) = config.general.metrics_port {
+            spawn_safe(metrics::serve(metrics_port));
+        }
+
+        // ✅ Keep the spend aggregates materialized, also for ops written outside the refresh policy windows
+        let aggregates_app = Arc::clone(&app);
+        let aggregate_refresh_interval = Duration::from_secs(config.general.aggregate_refresh_interval_secs);
+        spawn_safe(async move {
+            loop {
+                match aggregates_app.storage.refresh_spend_aggregates().await {
+                    Ok(()) => tracing::info!("📈 Spend aggregates refreshed"),
+                    Err(e) => tracing::error!("❌ Failed to refresh spend aggregates: {:?}", e),
+                }
+                sleep(aggregate_refresh_interval).await;
+            }
+        });
+
+        // ✅ Start Kafka consumer
+        let kafka_broker = config.storage.kafka_broker.clone();
+        let kafka_topics = config.storage.kafka_topics.clone();
+        let kafka_group_id = config.storage.kafka_group_id.clone();
+
+        tracing::info!("🟢 Starting Kafka consumer...");
+        spawn_safe(async move {
+            loop {
+                let kafka_app = Arc::clone(&app);
+                let result = AssertUnwindSafe(
+                    start_kafka_consumer(&kafka_broker, &kafka_topics[0], &kafka_group_id, kafka_app)
+                )
+                .catch_unwind()
+                .await;
+
+                if let Err(err) = result {
+                    tracing::error!("🔥 Kafka consumer panicked, restarting... {:?}", err);
+                } else {
+                    tracing::warn!("⚠️ Kafka consumer exited unexpectedly, restarting...");
+                }
+
+                sleep(Duration::from_secs(5)).await;
+            }
+        });
+
+        // ✅ Spawn per-chain listeners
+        for (chain_name, chain) in config.chains.clone() {
+            if chain.active {
+                let poll_interval = chain.block_time * chain.polling_blocks;
+                // ✅ Each chain has its own queues and workers, a slow chain does not hold up the others
+                let log_sender = ChainPipeline::spawn(&chain, Arc::clone(&event_processor)).sender;
+                let chain_clone = chain.clone();
+                let chain_name_clone = chain_name.clone();
+                let app_for_chain = Arc::clone(&indexer_app);
+
+                // ✅ Check paymaster deposits against the EntryPoint
+                let deposit_chain = chain.clone();
+                let deposit_app = Arc::clone(&indexer_app);
+                spawn_safe(async move {
+                    let deposit_monitor: EventListener<TimescaleStorage, RedisCoordinator> =
+                        EventListener::new(&deposit_chain, deposit_app).await;
+                    loop {
+                        sleep(Duration::from_secs(deposit_chain.deposit_check_interval_secs)).await;
+                        if let Err(e) = deposit_monitor.check_deposits(&deposit_chain).await {
+                            tracing::error!("❌ Deposit check failed for chain {}: {:?}", deposit_chain.chain_id, e);
+                        }
+                    }
+                });
+
+                spawn_safe(async move {
+                    // New heads wake the listener early when a WebSocket endpoint is configured
+                    let head_watcher = chain_clone
+                        .ws_url
+                        .clone()
+                        .filter(|url| !url.is_empty())
+                        .map(|ws_url| {
+                            let stale_after = Duration::from_secs((chain_clone.block_time * 30).max(30));
+                            HeadWatcher::spawn(chain_clone.chain_id, ws_url, stale_after)
+                        });
+
+                    // this loop ensures the listener restarts if it panics
+                    loop {
+                        let result = AssertUnwindSafe(async {
+                            let event_listener: EventListener<TimescaleStorage, RedisCoordinator> =
+                                EventListener::new(&chain_clone, Arc::clone(&app_for_chain)).await;
+                            loop {
+                                tracing::info!("🔍 Listening for events on {}...", chain_name_clone);
+                                event_listener
+                                    .listen_events(&chain_clone, log_sender.clone())
+                                    .await;
+                                match &head_watcher {
+                                    Some(watcher) => watcher.wait(Duration::from_secs(poll_interval)).await,
+                                    None => sleep(Duration::from_secs(poll_interval)).await,
+                                }
+                            }
+                        })
+                        .catch_unwind()
+                        .await;
+
+                        if let Err(err) = result {
+                            tracing::error!(
+                                "🔥 Chain listener for {} panicked, restarting... {:?}",
+                                chain_name_clone, err
+                            );
+                            sleep(Duration::from_secs(5)).await;
+                        }
+                    }
+                });
+            }
+        }
+
+        // ✅ Main keep-alive loop
+        loop {
+            sleep(Duration::from_secs(3600)).await;
+        }
+    }
+
+    /// **Backfill a historical range of one chain**
+    pub async fn backfill(self, chain_name: &str, job: BackfillJob) -> anyhow::Result<()> {
+        let Indexer { config, app, processor } = self;
+        let chain = config
+            .chains
+            .get(chain_name)
+            .cloned()
+            .with_context(|| format!("Unknown chain: {}", chain_name))?;
+        tracing::info!("⏪ Starting backfill {} on {} ({:?})", job.name, chain_name, job);
+
+        let pipeline = ChainPipeline::spawn(&chain, Arc::new(processor));
+
+        let event_listener: EventListener<TimescaleStorage, RedisCoordinator> =
+            EventListener::new(&chain, app).await;
+        let result = event_listener.backfill(&chain, &job, pipeline.sender.clone()).await;
+
+        // Let the processor drain what was already fetched
+        pipeline.join().await;
+        result.with_context(|| format!("Backfill {} on {} failed", job.name, chain_name))
+    }
+
+    /// **Run unresolved dead letters through the current handlers, oldest logs first**
+    ///
+    /// Entries that go through are resolved, the others keep their new error and attempt count.
+    pub async fn replay(self, source: Option<DeadLetterSource>, limit: i64) -> anyhow::Result<()> {
+        let Indexer { config, app, processor } = self;
+        let letters = app
+            .storage
+            .get_dead_letters(source, limit)
+            .await
+            .context("Failed to read dead letters")?;
+        tracing::info!("📮 Replaying {} dead letter(s)", letters.len());
+
+        let mut replayer = LogReplayer { config: &config, app: &app, processor: &processor, listeners: HashMap::new() };
+        let (mut resolved, mut failed) = (0, 0);
+        for letter in letters {
+            let result = match letter.source.as_str() {
+                "kafka" => handle_message(&letter.payload, &app).await,
+                _ => replayer.replay(&letter).await,
+            };
+            // Ok means the entry is stored: the Kafka path upserts its user op, see LogReplayer::replay for logs
+            match result {
+                Ok(()) => {
+                    app.storage.resolve_dead_letter(letter.id).await?;
+                    tracing::info!("✅ Replayed dead letter {} ({})", letter.id, letter.entry_key);
+                    resolved += 1;
+                }
+                Err(e) => {
+                    tracing::error!("❌ Dead letter {} ({}) failed again: {:?}", letter.id, letter.entry_key, e);
+                    app.storage.fail_dead_letter(letter.id, &format!("{:?}", e)).await?;
+                    failed += 1;
+                }
+            }
+        }
+
+        tracing::info!("📮 Replay done: {} resolved, {} still failing", resolved, failed);
+        Ok(())
+    }
+}
+
+/// **Rebuilds dead-lettered logs into events, with one listener per chain for transaction lookups**
+struct LogReplayer<'a> {
+    config: &'a Config,
+    app: &'a Arc<IndexerApp>,
+    processor: &'a ProcessEvent<TimescaleStorage, RedisCoordinator>,
+    listeners: HashMap<u32, EventListener<TimescaleStorage, RedisCoordinator>>,
+}
+
+impl LogReplayer<'_> {
+    /// **Replay a dead-lettered log, Ok once everything it carries is stored**
+    ///
+    /// Handlers store a log (or skip it when it is not about our contracts) before `handle` returns Ok, with
+    /// `ack: None` user ops are written right away instead of being staged for a checkpoint. Paymaster and revert
+    /// reason logs are the exception: they are only kept until the UserOperationEvent of their op, which stores
+    /// them, so those logs and UserOperationEvents are replayed with the op's other logs, fetched from the receipt.
+    async fn replay(&mut self, letter: &DeadLetterRecord) -> anyhow::Result<()> {
+        let stored: StoredLog = serde_json::from_str(&letter.payload).context("Invalid dead-lettered log")?;
+        let paired = stored.log.topic0().is_some_and(|topic| {
+            *topic == UserOperationEvent::SIGNATURE_HASH || UserOperationHandler::paired_signatures().contains(topic)
+        });
+        let tx_hash = match stored.log.transaction_hash {
+            Some(tx_hash) if paired => tx_hash,
+            _ => return self.handle(&stored, stored.log.clone(), None).await,
+        };
+
+        let (chain, listener) = self.listener(stored.chain_id).await?;
+        let logs = listener.receipt_logs(chain, tx_hash).await?;
+        // The logs between two UserOperationEvents of a bundle belong to the second, as for the paymaster correlator
+        let user_op_event = logs
+            .iter()
+            .find(|log| {
+                log.log_index >= stored.log.log_index && log.topic0() == Some(&UserOperationEvent::SIGNATURE_HASH)
+            })
+            .cloned()
+            .with_context(|| format!("No UserOperationEvent follows log {}, it cannot be stored", letter.entry_key))?;
+        // UserOperationEvent handling needs the bundle transaction, which is not kept with the log
+        let transaction = listener.transaction_info(tx_hash).await?.map(Arc::new);
+
+        for log in user_op_logs(&logs, user_op_event.log_index) {
+            self.handle(&stored, log.clone(), None).await?;
+        }
+        self.handle(&stored, user_op_event, transaction).await
+    }
+
+    /// **Run a log of the dead-lettered one's transaction through the handlers, user ops are stored right away**
+    async fn handle(&self, stored: &StoredLog, log: Log, transaction: Option<Arc<TransactionInfo>>) -> anyhow::Result<()> {
+        let event = Event {
+            chain_id: stored.chain_id,
+            log,
+            block_timestamp: stored.block_timestamp,
+            transaction,
+            ack: None,
+        };
+        self.processor.handle(&event).await
+    }
+
+    async fn listener(
+        &mut self,
+        chain_id: u32,
+    ) -> anyhow::Result<(&'_ ChainConfig, &EventListener<TimescaleStorage, RedisCoordinator>)> {
+        let chain = self
+            .config
+            .chains
+            .values()
+            .find(|chain| chain.chain_id == chain_id)
+            .with_context(|| format!("Chain {} is not configured", chain_id))?;
+        let listener = match self.listeners.entry(chain_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(EventListener::new(chain, Arc::clone(self.app)).await),
+        };
+        Ok((chain, listener))
+    }
+}
+
+/// **The logs of a bundle that belong to the UserOperationEvent at `log_index`**
+///
+/// Same rule as the paymaster correlator: the logs between two UserOperationEvents belong to the second.
+fn user_op_logs(logs: &[Log], log_index: Option<u64>) -> &[Log] {
+    let end = logs
+        .iter()
+        .position(|log| log.log_index.is_some() && log.log_index >= log_index)
+        .unwrap_or(logs.len());
+    let start = logs[..end]
+        .iter()
+        .rposition(|log| log.topic0() == Some(&UserOperationEvent::SIGNATURE_HASH))
+        .map_or(0, |i| i + 1);
+    &logs[start..end]
+}
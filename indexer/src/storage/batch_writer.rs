@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Utc};
+use sqlx::{types::BigDecimal, PgPool, Postgres, QueryBuilder, Transaction};
+use tokio::sync::{mpsc::{self, error::TryRecvError}, oneshot};
+use tokio::time::Instant;
+
+use crate::metrics;
+use crate::model::user_op::UserOpMessage;
+use crate::utils::{extract_meta_fields, MetaFields};
+
+// Postgres takes at most 65535 bind parameters per statement, a row binds 31
+const MAX_BATCH_SIZE: usize = 2000;
+
+type Reply = oneshot::Sender<Result<(), String>>;
+
+/// **Gathers concurrent user op messages and writes them in one transaction**
+///
+/// Messages are merged in arrival order with the status priority rules of `upsert_rows`.
+#[derive(Clone)]
+pub struct UserOpBatchWriter {
+    sender: mpsc::Sender<(UserOpMessage, Reply)>,
+    sending: Arc<AtomicUsize>, // writers between calling `write` and their message being queued
+}
+
+impl UserOpBatchWriter {
+    pub fn spawn(pool: PgPool, window: Duration, max_size: usize) -> Self {
+        let max_size = max_size.clamp(1, MAX_BATCH_SIZE);
+        let (sender, receiver) = mpsc::channel(max_size * 2);
+        let sending = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(run(pool, receiver, Arc::clone(&sending), window, max_size));
+        Self { sender, sending }
+    }
+
+    /// **Queue a message and wait until its batch is committed**
+    pub async fn write(&self, msg: UserOpMessage) -> Result<(), Error> {
+        let (reply, done) = oneshot::channel();
+        {
+            let _sending = Sending::start(&self.sending);
+            self.sender
+                .send((msg, reply))
+                .await
+                .map_err(|_| anyhow!("user op writer stopped"))?;
+        }
+        done.await
+            .map_err(|_| anyhow!("user op writer stopped"))?
+            .map_err(|e| anyhow!(e))
+    }
+}
+
+/// Counts a writer as sending until its message is queued or the send is dropped
+struct Sending<'a>(&'a AtomicUsize);
+
+impl<'a> Sending<'a> {
+    fn start(sending: &'a AtomicUsize) -> Self {
+        sending.fetch_add(1, Ordering::SeqCst);
+        Self(sending)
+    }
+}
+
+impl Drop for Sending<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+async fn run(
+    pool: PgPool,
+    mut receiver: mpsc::Receiver<(UserOpMessage, Reply)>,
+    sending: Arc<AtomicUsize>,
+    window: Duration,
+    max_size: usize,
+) {
+    let mut batch = Vec::with_capacity(max_size);
+    while let Some(first) = receiver.recv().await {
+        batch.push(first);
+        // The window starts with the first message and is only waited out while other writers are still sending,
+        // every queued writer waits for this batch, so nothing else would arrive
+        let deadline = Instant::now() + window;
+        while batch.len() < max_size {
+            match receiver.try_recv() {
+                Ok(item) => batch.push(item),
+                Err(TryRecvError::Empty) if sending.load(Ordering::SeqCst) > 0 => {
+                    match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                        Ok(Some(item)) => batch.push(item),
+                        _ => break,
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        flush(&pool, &mut batch).await;
+    }
+}
+
+async fn flush(pool: &PgPool, batch: &mut Vec<(UserOpMessage, Reply)>) {
+    let (messages, replies): (Vec<UserOpMessage>, Vec<Reply>) = batch.drain(..).unzip();
+    metrics::inc_counter("indexer_user_op_batches_total", "User op batches written to Timescale", &[], 1.0);
+    metrics::inc_counter(
+        "indexer_user_op_batch_messages_total",
+        "User op messages written to Timescale in batches",
+        &[],
+        messages.len() as f64,
+    );
+
+    match write_batch(pool, &messages, None).await {
+        Ok(()) => {
+            for reply in replies {
+                let _ = reply.send(Ok(()));
+            }
+        }
+        Err(e) if messages.len() == 1 => {
+            let _ = replies.into_iter().next().map(|reply| reply.send(Err(format!("{:?}", e))));
+        }
+        Err(e) => {
+            // One bad message must not fail its neighbours
+            tracing::warn!("⚠️ Batch of {} user ops failed, writing them one by one: {:?}", messages.len(), e);
+            for (msg, reply) in messages.iter().zip(replies) {
+                let result = write_batch(pool, std::slice::from_ref(msg), None).await;
+                let _ = reply.send(result.map_err(|e| format!("{:?}", e)));
+            }
+        }
+    }
+}
+
+
+/// One message, as bound into the upsert
+struct UserOpRow<'a> {
+    msg: &'a UserOpMessage,
+    user_op_hash: &'a str,
+    time: DateTime<Utc>,
+    status: String,
+    paymaster_mode: Option<String>,
+    block_number: Option<i64>,
+    meta: MetaFields,
+    native_usd_price: Option<BigDecimal>,
+    deployment_guess: bool,
+}
+
+impl<'a> UserOpRow<'a> {
+    fn new(msg: &'a UserOpMessage) -> Self {
+        let meta = msg.meta_data
+            .as_ref()
+            .and_then(|v| v.as_object())
+            .map(extract_meta_fields)
+            .unwrap_or_default();
+
+        // Once the op is mined the AccountDeployed event decides, before that the user op JSON is the best guess:
+        // deployment is assumed if `factory`, `factoryData` or v0.6 `initCode` is present.
+        let deployment_guess = ["factory", "factoryData", "initCode"].iter().any(|key| {
+            msg.user_op.get(*key)
+                .and_then(|v| v.as_str())
+                .map(|s| !s.is_empty() && s != "0x")
+                .unwrap_or(false)
+        });
+
+        Self {
+            msg,
+            user_op_hash: msg.user_op_hash.trim(),
+            time: msg.timestamp.parse::<DateTime<Utc>>().unwrap_or_else(|_| Utc::now()),
+            status: msg.status.to_string(),
+            paymaster_mode: msg.paymaster_mode.as_ref().map(|m| m.to_string()),
+            block_number: msg.block_number.map(|b| b as i64),
+            meta,
+            native_usd_price: msg.native_usd_price.as_deref().and_then(|s| BigDecimal::from_str(s).ok()),
+            deployment_guess,
+        }
+    }
+}
+
+/// **Indexing progress committed together with a batch**
+pub struct Checkpoint<'a> {
+    pub chain_id: u32,
+    pub cursor: &'a str,
+    pub block_number: u64,
+}
+
+/// **Upsert a batch into pm_user_operations and record the checkpoint if any, all or nothing**
+///
+/// An op seen twice in a batch goes to a second statement, as one statement can only update a row once.
+pub async fn write_batch(pool: &PgPool, messages: &[UserOpMessage], checkpoint: Option<&Checkpoint<'_>>) -> Result<(), Error> {
+    let mut rounds: Vec<Vec<UserOpRow>> = Vec::new();
+    let mut seen: HashMap<(u32, &str), usize> = HashMap::new();
+    for msg in messages {
+        let row = UserOpRow::new(msg);
+        tracing::info!("🟢 Upserting UserOpMessage with hash: {}", row.user_op_hash);
+        tracing::debug!("- useropmessage: {}", serde_json::to_string(msg).unwrap_or_default());
+
+        let round = seen.entry((msg.chain_id, row.user_op_hash)).or_insert(0);
+        if rounds.len() <= *round {
+            rounds.push(Vec::new());
+        }
+        rounds[*round].push(row);
+        *round += 1;
+    }
+
+    let mut tx = pool.begin().await?;
+    for rows in rounds.iter().flat_map(|rows| rows.chunks(MAX_BATCH_SIZE)) {
+        upsert_rows(&mut tx, rows).await?;
+    }
+    if let Some(checkpoint) = checkpoint {
+        sqlx::query(
+            "INSERT INTO indexer_checkpoints (chain_id, cursor, block_number) VALUES ($1, $2, $3) \
+             ON CONFLICT (chain_id, cursor) DO UPDATE SET block_number = EXCLUDED.block_number"
+        )
+        .bind(checkpoint.chain_id as i32)
+        .bind(checkpoint.cursor)
+        .bind(checkpoint.block_number as i64)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// **Insert or merge user ops in one statement, safe against concurrent writers**
+///
+/// user_op_keys pins the hypertable time of every op, so its row is unique on (chain_id, user_op_hash, time)
+/// and a second writer lands in ON CONFLICT instead of inserting a duplicate. Claiming the key locks it until
+/// commit, so writes of the same op are serialized, also across indexer replicas. On conflict a message with
+/// a higher status priority overwrites the op, any other one only fills in what is missing; metadata is merged
+/// either way.
+async fn upsert_rows(tx: &mut Transaction<'_, Postgres>, rows: &[UserOpRow<'_>]) -> Result<(), Error> {
+    let mut query = QueryBuilder::<Postgres>::new(
+        "WITH v (time, chain_id, user_op_hash, user_operation, org_id, credential_id, paymaster_mode,\
+                 paymaster_id, policy_id, status, data_source, actual_gas_cost, actual_gas_used, deducted_user, deducted_amount,\
+                 token, premium, token_charge, applied_markup, exchange_rate, native_usd_price, metadata,\
+                 deployment_guess, block_number, block_hash, tx_hash, log_index, bundler, beneficiary,\
+                 effective_gas_price, entry_point_version) AS ("
+    );
+    query.push_values(rows, |mut row, r| {
+        row.push_bind(r.time)
+            .push_bind(r.msg.chain_id as i32)
+            .push_bind(r.user_op_hash)
+            .push_bind(&r.msg.user_op)
+            .push_bind(&r.msg.org_id)
+            .push_bind(&r.msg.credential_id)
+            .push_bind(&r.paymaster_mode)
+            .push_bind(&r.msg.paymaster_id)
+            .push_bind(&r.msg.policy_id)
+            .push_bind(&r.status)
+            .push_bind(&r.msg.data_source)
+            .push_bind(&r.meta.actual_gas_cost)
+            .push_bind(&r.meta.actual_gas_used)
+            .push_bind(&r.meta.deducted_user)
+            .push_bind(&r.meta.deducted_amount)
+            .push_bind(&r.meta.token)
+            .push_bind(&r.meta.premium)
+            .push_bind(&r.meta.token_charge)
+            .push_bind(&r.meta.applied_markup)
+            .push_bind(&r.meta.exchange_rate)
+            .push_bind(&r.native_usd_price)
+            .push_bind(r.msg.meta_data.as_ref().unwrap_or(&serde_json::Value::Null))
+            .push_bind(r.deployment_guess)
+            .push_bind(r.block_number)
+            .push_bind(&r.msg.block_hash)
+            .push_bind(&r.meta.tx_hash)
+            .push_bind(r.meta.log_index)
+            .push_bind(&r.meta.bundler)
+            .push_bind(&r.meta.beneficiary)
+            .push_bind(&r.meta.effective_gas_price)
+            .push_bind(&r.msg.entry_point_version);
+    });
+    query.push(
+        "),
+         keys AS (
+            INSERT INTO user_op_keys (chain_id, user_op_hash, time)
+            SELECT chain_id, user_op_hash, time FROM v
+            ON CONFLICT (chain_id, user_op_hash) DO UPDATE SET time = user_op_keys.time
+            RETURNING chain_id, user_op_hash, time
+         )
+         INSERT INTO pm_user_operations AS t
+         (time, chain_id, user_op_hash, user_operation, org_id, credential_id, paymaster_mode,
+          paymaster_id, policy_id, status, data_source,
+          actual_gas_cost, actual_gas_used, deducted_user, deducted_amount, usd_amount,
+          token, premium, token_charge, applied_markup, exchange_rate, native_usd_price, metadata, account_deployed,
+          block_number, block_hash, tx_hash, log_index, bundler, beneficiary, effective_gas_price,
+          entry_point_version)
+         SELECT keys.time, v.chain_id, v.user_op_hash, v.user_operation, v.org_id, v.credential_id, v.paymaster_mode,
+                v.paymaster_id, v.policy_id, v.status, v.data_source,
+                v.actual_gas_cost, v.actual_gas_used, v.deducted_user, v.deducted_amount,
+                v.actual_gas_cost * v.native_usd_price * 1e-18,
+                v.token, v.premium, v.token_charge, v.applied_markup, v.exchange_rate, v.native_usd_price, v.metadata,
+                COALESCE(
+                    CASE WHEN v.block_number IS NOT NULL
+                         THEN EXISTS (SELECT 1 FROM smart_accounts s WHERE s.user_op_hash = v.user_op_hash) END,
+                    v.deployment_guess
+                ),
+                v.block_number, v.block_hash, v.tx_hash, v.log_index, v.bundler, v.beneficiary, v.effective_gas_price,
+                v.entry_point_version
+         FROM v JOIN keys ON keys.chain_id = v.chain_id AND keys.user_op_hash = v.user_op_hash
+         ON CONFLICT (chain_id, user_op_hash, time) DO UPDATE
+         SET status = CASE WHEN user_op_status_priority(EXCLUDED.status) > user_op_status_priority(t.status)
+                           THEN EXCLUDED.status ELSE t.status END,
+             data_source = CASE WHEN user_op_status_priority(EXCLUDED.status) > user_op_status_priority(t.status)
+                                THEN EXCLUDED.data_source ELSE t.data_source END,
+             metadata = t.metadata || EXCLUDED.metadata,",
+    );
+    // Fields a higher status overwrites as they are, including with NULL
+    for column in [
+        "actual_gas_cost", "actual_gas_used", "deducted_user", "deducted_amount", "token",
+        "premium", "token_charge", "applied_markup", "exchange_rate",
+    ] {
+        query.push(format!(
+            " {column} = CASE WHEN user_op_status_priority(EXCLUDED.status) > user_op_status_priority(t.status) \
+              THEN EXCLUDED.{column} ELSE t.{column} END,"
+        ));
+    }
+    // Fields only a lower or equal status fills in
+    for column in ["org_id", "paymaster_mode", "paymaster_id", "policy_id", "credential_id"] {
+        query.push(format!(
+            " {column} = CASE WHEN user_op_status_priority(EXCLUDED.status) > user_op_status_priority(t.status) \
+              THEN t.{column} ELSE COALESCE(t.{column}, EXCLUDED.{column}) END,"
+        ));
+    }
+    // Fields where the higher status wins, but nothing is cleared
+    for column in [
+        "block_number", "block_hash", "tx_hash", "log_index", "bundler", "beneficiary",
+        "effective_gas_price", "entry_point_version",
+    ] {
+        query.push(format!(
+            " {column} = CASE WHEN user_op_status_priority(EXCLUDED.status) > user_op_status_priority(t.status) \
+              THEN COALESCE(EXCLUDED.{column}, t.{column}) ELSE COALESCE(t.{column}, EXCLUDED.{column}) END,"
+        ));
+    }
+    // The USD amount falls back on the stored gas cost and price when the message lacks them
+    query.push(
+        " usd_amount = CASE WHEN user_op_status_priority(EXCLUDED.status) > user_op_status_priority(t.status)
+              THEN COALESCE(COALESCE(EXCLUDED.actual_gas_cost, t.actual_gas_cost)
+                            * COALESCE(EXCLUDED.native_usd_price, t.native_usd_price) * 1e-18, t.usd_amount)
+              ELSE COALESCE(t.usd_amount, COALESCE(EXCLUDED.actual_gas_cost, t.actual_gas_cost)
+                            * COALESCE(EXCLUDED.native_usd_price, t.native_usd_price) * 1e-18) END,
+          native_usd_price = CASE WHEN user_op_status_priority(EXCLUDED.status) > user_op_status_priority(t.status)
+              THEN t.native_usd_price ELSE COALESCE(t.native_usd_price, EXCLUDED.native_usd_price) END,
+          account_deployed = CASE WHEN user_op_status_priority(EXCLUDED.status) > user_op_status_priority(t.status)
+              THEN CASE WHEN EXCLUDED.block_number IS NOT NULL THEN EXCLUDED.account_deployed ELSE t.account_deployed END
+              ELSE COALESCE(t.account_deployed, EXCLUDED.account_deployed) END",
+    );
+    query.build().execute(&mut **tx).await?;
+    Ok(())
+}
@@ -1,10 +1,48 @@
+pub mod batch_writer;
 pub mod time_scale;
 
 use anyhow::Error;
 use async_trait::async_trait;
-use crate::model::user_op::UserOpMessage;
+use sqlx::types::BigDecimal;
+use crate::model::{contract_event::ContractEvent, dead_letter::{DeadLetter, DeadLetterRecord, DeadLetterSource}, ledger::LedgerEntry, paymaster_deposit::DepositEvent, revert_reason::UserOpRevertReason, smart_account::SmartAccount, spend::{SpendBucket, SpendQuery}, sponsorship::{GasRefund, UserOpSponsorship}, user_op::UserOpMessage};
 
 #[async_trait]
 pub trait Storage {
     async fn upsert_user_op_message(&self, msg: UserOpMessage) -> Result<(), Error>;
-}
\ No newline at end of file
+    /// Records a deployed account, keeping the earliest sighting, and flags its user op as deploying it
+    async fn upsert_smart_account(&self, account: SmartAccount) -> Result<(), Error>;
+    async fn upsert_revert_reason(&self, reason: UserOpRevertReason) -> Result<(), Error>;
+    async fn upsert_sponsorship(&self, sponsorship: UserOpSponsorship) -> Result<(), Error>;
+    /// Stores a refund against its user, a refund that was already stored is left as is
+    async fn insert_refund(&self, refund: GasRefund) -> Result<(), Error>;
+    /// Books a prepaid balance movement on both the user and the paymaster side, a booked log is left as is
+    async fn record_ledger_entry(&self, entry: LedgerEntry) -> Result<(), Error>;
+    /// Links the unlinked refunds and ledger entries of `user` logged before `before_log_index` in a bundle
+    /// to its user op, returning how many refunds were linked
+    async fn link_to_user_op(&self, chain_id: u32, tx_hash: &str, before_log_index: u64, user: &str, user_op_hash: &str) -> Result<u64, Error>;
+    /// Stores a generically decoded log, a log that was already stored is left as is
+    async fn insert_contract_event(&self, event: ContractEvent) -> Result<(), Error>;
+    /// Records a deposit or stake change and recomputes the paymaster's deposit, in any order and at most once per log
+    async fn apply_deposit_event(&self, event: DepositEvent) -> Result<(), Error>;
+    /// Records the on-chain balance at `block_number` as the deposit to count from, returning the previous value
+    async fn reconcile_deposit(&self, chain_id: u32, entry_point: &str, paymaster: &str, deposit: BigDecimal, block_number: u64) -> Result<Option<BigDecimal>, Error>;
+    /// Records a failed input, or one more attempt of it, returning its attempt count
+    async fn record_dead_letter(&self, letter: DeadLetter) -> Result<i32, Error>;
+    /// Unresolved dead letters, logs in chain order
+    async fn get_dead_letters(&self, source: Option<DeadLetterSource>, limit: i64) -> Result<Vec<DeadLetterRecord>, Error>;
+    async fn resolve_dead_letter(&self, id: i64) -> Result<(), Error>;
+    /// Resolves the dead letter of an input that went through on a later attempt
+    async fn resolve_dead_letter_key(&self, source: DeadLetterSource, entry_key: &str) -> Result<(), Error>;
+    /// Counts a failed replay of a dead letter
+    async fn fail_dead_letter(&self, id: i64, error: &str) -> Result<(), Error>;
+    /// Materializes the spend aggregates where ops changed since their last refresh, hourly before daily
+    async fn refresh_spend_aggregates(&self) -> Result<(), Error>;
+    /// Spend buckets in [from, to), oldest first
+    async fn get_spend(&self, query: SpendQuery) -> Result<Vec<SpendBucket>, Error>;
+    /// Last fully stored block of an indexing cursor (`live` or `backfill:{job}`)
+    async fn get_checkpoint(&self, chain_id: u32, cursor: &str) -> Result<Option<u64>, Error>;
+    /// Records a fully stored block, writing the block range's staged user ops in the same transaction
+    async fn set_checkpoint(&self, chain_id: u32, cursor: &str, block_number: u64, user_ops: Vec<UserOpMessage>) -> Result<(), Error>;
+    /// Rolls back user ops and other rows indexed at or above `from_block`, and the checkpoints past it, returning the affected user op hashes
+    async fn rollback_from_block(&self, chain_id: u32, from_block: u64) -> Result<Vec<String>, Error>;
+}
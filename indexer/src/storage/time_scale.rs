@@ -2,21 +2,31 @@
 
 use anyhow::Error;
 use async_trait::async_trait;
-use sqlx::{types::BigDecimal, PgPool};
-use crate::{model::user_op::{UserOpMessage, Status, UserOperationRecord}, storage::Storage};
+use sqlx::{types::BigDecimal, Executor, PgPool, Postgres, Transaction};
+use crate::{model::{contract_event::ContractEvent, dead_letter::{DeadLetter, DeadLetterRecord, DeadLetterSource}, ledger::{LedgerEntry, LedgerReason}, paymaster_deposit::{DepositChange, DepositEvent}, revert_reason::UserOpRevertReason, smart_account::SmartAccount, spend::{SpendBucket, SpendQuery}, sponsorship::{GasRefund, UserOpSponsorship}, user_op::{UserOpMessage, Status}}, storage::Storage};
 use chrono::{DateTime, Utc};
-use crate::utils::{calculate_usd_spent, extract_meta_fields};
+use crate::config::config::StorageConfig;
+use crate::storage::batch_writer::{write_batch, Checkpoint, UserOpBatchWriter};
+use std::time::Duration;
 use std::str::FromStr;
+use std::collections::BTreeSet;
 
 #[derive(Clone)]
 pub struct TimescaleStorage {
     pool: PgPool,
+    user_ops: UserOpBatchWriter,
 }
 
 impl TimescaleStorage {
-    pub async fn new(database_url: &str) -> Self {
-        let pool = PgPool::connect(database_url).await.expect("Failed to connect to DB");
-        Self { pool }
+    pub async fn new(config: &StorageConfig) -> Self {
+        let pool = PgPool::connect(&config.timescale_db_url).await.expect("Failed to connect to DB");
+        Self::from_pool(pool, Duration::from_millis(config.user_op_batch_window_ms), config.user_op_batch_size)
+    }
+
+    /// **Storage on an existing pool, e.g. the API's, with its user op writer**
+    pub fn from_pool(pool: PgPool, user_op_batch_window: Duration, user_op_batch_size: usize) -> Self {
+        let user_ops = UserOpBatchWriter::spawn(pool.clone(), user_op_batch_window, user_op_batch_size);
+        Self { pool, user_ops }
     }
 }
 
@@ -28,166 +38,636 @@ impl TimescaleStorage {
 
 #[async_trait]
 impl Storage for TimescaleStorage {
-    async fn upsert_user_op_message(&self, mut msg: UserOpMessage) -> Result<(), Error> {
-        let chain_id = msg.chain_id as i32;
-        let user_op_hash = msg.user_op_hash.trim();
-        let event_time = msg.timestamp.parse::<DateTime<Utc>>().unwrap_or_else(|_| Utc::now());
-        let status_str = msg.status.to_string();
-        let paymaster_mode = msg.paymaster_mode.as_ref().map(|m| m.to_string());
-
-        tracing::info!("🟢 Upserting UserOpMessage with hash: {}", user_op_hash);
-        tracing::debug!("- useropmessage: {}", serde_json::to_string(&msg).unwrap_or_default());
-
-        let existing: Option<UserOperationRecord> = sqlx::query_as::<_, UserOperationRecord>(
-            "SELECT status, native_usd_price, actual_gas_cost, usd_amount \
-             FROM pm_user_operations \
-             WHERE user_op_hash = $1"
-        )
-        .bind(&user_op_hash)
-        .fetch_optional(&self.pool)
+    async fn upsert_user_op_message(&self, msg: UserOpMessage) -> Result<(), Error> {
+        self.user_ops.write(msg).await
+    }
+
+    async fn upsert_smart_account(&self, account: SmartAccount) -> Result<(), Error> {
+        let first_seen_at = account.timestamp.parse::<DateTime<Utc>>().unwrap_or_else(|_| Utc::now());
+        let user_op_hash = account.user_op_hash.trim();
+        tracing::info!("🟢 Upserting smart account {} on chain {}", account.sender, account.chain_id);
+
+        let mut tx = self.pool.begin().await?;
+
+        // A backfill can see an account after live indexing did, keep the earliest deployment
+        sqlx::query(
+            "INSERT INTO smart_accounts \
+             (chain_id, sender, factory, user_op_hash, paymaster, first_seen_block, first_seen_at, tx_hash) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+             ON CONFLICT (chain_id, sender) DO UPDATE \
+             SET factory = EXCLUDED.factory, user_op_hash = EXCLUDED.user_op_hash, paymaster = EXCLUDED.paymaster,\
+                 first_seen_block = EXCLUDED.first_seen_block, first_seen_at = EXCLUDED.first_seen_at,\
+                 tx_hash = EXCLUDED.tx_hash \
+             WHERE smart_accounts.first_seen_block > EXCLUDED.first_seen_block"
+        )
+        .bind(account.chain_id as i32)
+        .bind(&account.sender)
+        .bind(&account.factory)
+        .bind(user_op_hash)
+        .bind(&account.paymaster)
+        .bind(account.block_number as i64)
+        .bind(first_seen_at)
+        .bind(&account.tx_hash)
+        .execute(&mut *tx)
         .await?;
 
-        let db_native_price = existing.as_ref().and_then(|e| e.native_usd_price.clone());
-        let db_actual_gas_cost = existing.as_ref().and_then(|e| e.actual_gas_cost);
+        // The user op row may not exist yet, its upsert then reads the flag from smart_accounts
+        sqlx::query("UPDATE pm_user_operations SET account_deployed = TRUE WHERE user_op_hash = $1")
+            .bind(user_op_hash)
+            .execute(&mut *tx)
+            .await?;
 
-        let actual_gas_cost_str = msg.meta_data
-            .as_ref()
-            .and_then(|m| m.get("actualGasCost"))
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string())
-            .or_else(|| db_actual_gas_cost.map(|v| v.to_string()));
+        tx.commit().await?;
+        Ok(())
+    }
 
-        let native_price = msg.native_usd_price
-            .as_deref()
-            .and_then(|s| BigDecimal::from_str(s).ok())
-            .or(db_native_price);
+    async fn upsert_revert_reason(&self, reason: UserOpRevertReason) -> Result<(), Error> {
+        let time = reason.timestamp.parse::<DateTime<Utc>>().unwrap_or_else(|_| Utc::now());
+        let nonce = BigDecimal::from_str(&reason.nonce)?;
+        tracing::info!("🟢 Upserting {} revert reason for {}", reason.stage.as_str(), reason.user_op_hash);
 
-        if msg.native_usd_price.is_none() {
-            msg.native_usd_price = native_price.as_ref().map(|v| format!("{:.6}", v));
-        }
+        sqlx::query(
+            "INSERT INTO user_op_revert_reasons \
+             (time, chain_id, user_op_hash, stage, sender, nonce, reason_type, reason, selector, revert_data, block_number, tx_hash) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) \
+             ON CONFLICT (chain_id, user_op_hash, stage) DO UPDATE \
+             SET time = EXCLUDED.time, reason_type = EXCLUDED.reason_type, reason = EXCLUDED.reason,\
+                 selector = EXCLUDED.selector, revert_data = EXCLUDED.revert_data,\
+                 block_number = EXCLUDED.block_number, tx_hash = EXCLUDED.tx_hash"
+        )
+        .bind(time)
+        .bind(reason.chain_id as i32)
+        .bind(reason.user_op_hash.trim())
+        .bind(reason.stage.as_str())
+        .bind(&reason.sender)
+        .bind(&nonce)
+        .bind(&reason.reason_type)
+        .bind(&reason.reason)
+        .bind(&reason.selector)
+        .bind(&reason.revert_data)
+        .bind(reason.block_number.map(|b| b as i64))
+        .bind(&reason.tx_hash)
+        .execute(&self.pool)
+        .await?;
 
-        let usd_amount_to_store = calculate_usd_spent(
-            native_price.as_ref().map(|v| v.to_string()).as_deref().unwrap_or(""),
-            actual_gas_cost_str.as_deref().unwrap_or("")
-        ).and_then(|s| BigDecimal::from_str(&s.to_string()).ok());
-
-        let (
-            actual_gas_cost, actual_gas_used, deducted_user, deducted_amount,
-            token, premium, token_charge, applied_markup, exchange_rate
-        ) = msg.meta_data
-            .as_ref()
-            .and_then(|v| v.as_object())
-            .map_or((None, None, None, None, None, None, None, None, None), |m| extract_meta_fields(m));
-
-        // Heuristic for account deployment: assumes if either `factory` or `factoryData` is present, deployment was intended.
-        let account_deployed = msg.user_op.get("factory")
-            .and_then(|v| v.as_str())
-            .map(|s| !s.is_empty() && s != "0x")
-            .unwrap_or(false)
-            ||
-            msg.user_op.get("factoryData")
-            .and_then(|v| v.as_str())
-            .map(|s| !s.is_empty() && s != "0x")
-            .unwrap_or(false);
-
-        if let Some(e) = existing {
-            let current_status = Status::from_str_case_insensitive(e.status.as_deref().unwrap_or_default());
-            let incoming_priority = msg.status.priority();
-            let existing_priority = current_status.priority();
-
-            let coalesced_usd_amount = usd_amount_to_store.or(e.usd_amount);
-            let coalesced_native_price = native_price.or(e.native_usd_price.clone());
-
-            let update_query = sqlx::query(
-                "UPDATE pm_user_operations \
-                 SET status = $1, data_source = $2,\
-                     metadata = metadata || $3::jsonb,\
-                     actual_gas_cost = $4, actual_gas_used = $5, deducted_user = $6,\
-                     deducted_amount = $7, usd_amount = $8, token = $9,\
-                     premium = $10, token_charge = $11, applied_markup = $12, exchange_rate = $13 \
-                 WHERE user_op_hash = $14"
-            )
-            .bind(&status_str)
-            .bind(&msg.data_source)
-            .bind(msg.meta_data.as_ref().unwrap_or(&serde_json::Value::Null))
-            .bind(&actual_gas_cost)
-            .bind(&actual_gas_used)
-            .bind(&deducted_user)
-            .bind(&deducted_amount)
-            .bind(&coalesced_usd_amount)
-            .bind(&token)
-            .bind(&premium)
-            .bind(&token_charge)
-            .bind(&applied_markup)
-            .bind(&exchange_rate)
-            .bind(&user_op_hash);
-
-            if incoming_priority > existing_priority {
-                update_query.execute(&self.pool).await?;
-            } else {
-                sqlx::query(
-                    "UPDATE pm_user_operations
-                    SET org_id = COALESCE(org_id, $1),
-                        paymaster_mode = COALESCE(paymaster_mode,$2),
-                        paymaster_id = COALESCE(paymaster_id, $3),
-                        credential_id = COALESCE(credential_id, $4),
-                        metadata = metadata || $5::jsonb,
-                        usd_amount = COALESCE(usd_amount, $6),
-                        native_usd_price = COALESCE(native_usd_price, $7),
-                        account_deployed = COALESCE(account_deployed, $8)
-                    WHERE user_op_hash = $9"
-                )
-                .bind(&msg.org_id)
-                .bind(&paymaster_mode)
-                .bind(&msg.paymaster_id)
-                .bind(&msg.credential_id)
-                .bind(msg.meta_data.as_ref().unwrap_or(&serde_json::Value::Null))
-                .bind(&coalesced_usd_amount)
-                .bind(&coalesced_native_price)
-                .bind(&account_deployed)
-                .bind(&user_op_hash)
-                .execute(&self.pool)
-                .await?;
+        Ok(())
+    }
+
+    async fn upsert_sponsorship(&self, sponsorship: UserOpSponsorship) -> Result<(), Error> {
+        let time = sponsorship.timestamp.parse::<DateTime<Utc>>().unwrap_or_else(|_| Utc::now());
+        tracing::info!("🟢 Upserting sponsorship of {} for {}", sponsorship.user_op_hash, sponsorship.user);
+
+        sqlx::query(
+            "INSERT INTO user_op_sponsorships \
+             (time, chain_id, user_op_hash, user_address, paymaster, block_number, tx_hash, log_index) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+             ON CONFLICT (chain_id, user_op_hash) DO UPDATE \
+             SET time = EXCLUDED.time, user_address = EXCLUDED.user_address, paymaster = EXCLUDED.paymaster,\
+                 block_number = EXCLUDED.block_number, tx_hash = EXCLUDED.tx_hash, log_index = EXCLUDED.log_index"
+        )
+        .bind(time)
+        .bind(sponsorship.chain_id as i32)
+        .bind(sponsorship.user_op_hash.trim())
+        .bind(&sponsorship.user)
+        .bind(&sponsorship.paymaster)
+        .bind(sponsorship.block_number as i64)
+        .bind(&sponsorship.tx_hash)
+        .bind(sponsorship.log_index as i32)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn insert_refund(&self, refund: GasRefund) -> Result<(), Error> {
+        let time = refund.timestamp.parse::<DateTime<Utc>>().unwrap_or_else(|_| Utc::now());
+        tracing::info!("🟢 Inserting refund of {} to {} on chain {}", refund.amount, refund.user, refund.chain_id);
+
+        sqlx::query(
+            "INSERT INTO paymaster_refunds \
+             (time, chain_id, paymaster, user_address, amount, block_number, tx_hash, log_index) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+             ON CONFLICT (chain_id, tx_hash, log_index) DO NOTHING"
+        )
+        .bind(time)
+        .bind(refund.chain_id as i32)
+        .bind(&refund.paymaster)
+        .bind(&refund.user)
+        .bind(&refund.amount)
+        .bind(refund.block_number as i64)
+        .bind(&refund.tx_hash)
+        .bind(refund.log_index as i32)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn record_ledger_entry(&self, entry: LedgerEntry) -> Result<(), Error> {
+        let time = entry.timestamp.parse::<DateTime<Utc>>().unwrap_or_else(|_| Utc::now());
+        let user_delta = match entry.reason {
+            LedgerReason::Deduction => -entry.amount.clone(),
+            LedgerReason::Refund => entry.amount.clone(),
+        };
+        let paymaster_delta = -user_delta.clone();
+        tracing::info!("🟢 Booking {} of {} for {} on chain {}", entry.reason.as_str(), entry.amount, entry.user, entry.chain_id);
+
+        // Both sides in one statement, the ledger never holds half an entry
+        sqlx::query(
+            "INSERT INTO prepaid_gas_ledger \
+             (time, chain_id, paymaster, user_address, account, delta, reason, block_number, tx_hash, log_index) \
+             VALUES ($1, $2, $3, $4, 'user', $5, $7, $8, $9, $10), \
+                    ($1, $2, $3, $4, 'paymaster', $6, $7, $8, $9, $10) \
+             ON CONFLICT (chain_id, tx_hash, log_index, account) DO NOTHING"
+        )
+        .bind(time)
+        .bind(entry.chain_id as i32)
+        .bind(&entry.paymaster)
+        .bind(&entry.user)
+        .bind(&user_delta)
+        .bind(&paymaster_delta)
+        .bind(entry.reason.as_str())
+        .bind(entry.block_number as i64)
+        .bind(&entry.tx_hash)
+        .bind(entry.log_index as i32)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn link_to_user_op(
+        &self,
+        chain_id: u32,
+        tx_hash: &str,
+        before_log_index: u64,
+        user: &str,
+        user_op_hash: &str,
+    ) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Ops of a bundle are handled in log order, so earlier ops already took their entries
+        let linked = sqlx::query(
+            "UPDATE paymaster_refunds SET user_op_hash = $5 \
+             WHERE chain_id = $1 AND tx_hash = $2 AND log_index < $3 \
+               AND LOWER(user_address) = LOWER($4) AND user_op_hash IS NULL"
+        )
+        .bind(chain_id as i32)
+        .bind(tx_hash)
+        .bind(before_log_index as i32)
+        .bind(user)
+        .bind(user_op_hash.trim())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        sqlx::query(
+            "UPDATE prepaid_gas_ledger SET user_op_hash = $5 \
+             WHERE chain_id = $1 AND tx_hash = $2 AND log_index < $3 \
+               AND LOWER(user_address) = LOWER($4) AND user_op_hash IS NULL"
+        )
+        .bind(chain_id as i32)
+        .bind(tx_hash)
+        .bind(before_log_index as i32)
+        .bind(user)
+        .bind(user_op_hash.trim())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(linked)
+    }
+
+    async fn insert_contract_event(&self, event: ContractEvent) -> Result<(), Error> {
+        let time = event.timestamp.parse::<DateTime<Utc>>().unwrap_or_else(|_| Utc::now());
+        tracing::info!("🟢 Inserting {} from {} on chain {}", event.event_name, event.contract_address, event.chain_id);
+
+        sqlx::query(
+            "INSERT INTO contract_events \
+             (time, chain_id, contract_address, event_name, event_signature, block_number, block_hash, tx_hash, log_index, args) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
+             ON CONFLICT (chain_id, block_number, log_index, time) DO NOTHING"
+        )
+        .bind(time)
+        .bind(event.chain_id as i32)
+        .bind(&event.contract_address)
+        .bind(&event.event_name)
+        .bind(&event.event_signature)
+        .bind(event.block_number as i64)
+        .bind(&event.block_hash)
+        .bind(&event.tx_hash)
+        .bind(event.log_index as i32)
+        .bind(&event.args)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn apply_deposit_event(&self, event: DepositEvent) -> Result<(), Error> {
+        tracing::info!("🟢 Applying {:?} for paymaster {} on chain {}", event.change, event.paymaster, event.chain_id);
+
+        let (change, amount, seconds): (&str, Option<&BigDecimal>, Option<i64>) = match &event.change {
+            DepositChange::Deposited { total_deposit } => ("DEPOSITED", Some(total_deposit), None),
+            DepositChange::Withdrawn { amount } => ("WITHDRAWN", Some(amount), None),
+            DepositChange::GasCharged { amount } => ("GAS_CHARGED", Some(amount), None),
+            DepositChange::StakeLocked { total_staked, unstake_delay_sec } => {
+                ("STAKE_LOCKED", Some(total_staked), Some(*unstake_delay_sec as i64))
             }
-        } else {
-            sqlx::query(
-                "INSERT INTO pm_user_operations \
-                 (time, chain_id, user_op_hash, user_operation, org_id, credential_id, paymaster_mode, \
-                  paymaster_id, status, data_source, \
-                  actual_gas_cost, actual_gas_used, deducted_user, deducted_amount, usd_amount, \
-                  token, premium, token_charge, applied_markup, exchange_rate, native_usd_price, metadata, account_deployed) \
-                 VALUES (\
-                    $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11,\
-                    $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23\
-                 )"
-            )
-            .bind(&event_time)
-            .bind(&chain_id)
-            .bind(&user_op_hash)
-            .bind(&msg.user_op)
-            .bind(&msg.org_id)
-            .bind(&msg.credential_id)
-            .bind(&paymaster_mode)
-            .bind(&msg.paymaster_id)
-            .bind(&status_str)
-            .bind(&msg.data_source)
-            .bind(&actual_gas_cost)
-            .bind(&actual_gas_used)
-            .bind(&deducted_user)
-            .bind(&deducted_amount)
-            .bind(&usd_amount_to_store)
-            .bind(&token)
-            .bind(&premium)
-            .bind(&token_charge)
-            .bind(&applied_markup)
-            .bind(&exchange_rate)
-            .bind(&native_price)
-            .bind(msg.meta_data.as_ref().unwrap_or(&serde_json::Value::Null))
-            .bind(&account_deployed)
+            DepositChange::StakeUnlocked { withdraw_time } => ("STAKE_UNLOCKED", None, Some(*withdraw_time as i64)),
+            DepositChange::StakeWithdrawn => ("STAKE_WITHDRAWN", None, None),
+        };
+
+        let mut tx = self.pool.begin().await?;
+        lock_deposit_row(&mut tx, event.chain_id, &event.entry_point, &event.paymaster).await?;
+
+        // A log indexed twice (retry, backfill over live) is only counted once
+        sqlx::query(
+            "INSERT INTO paymaster_deposit_events \
+             (chain_id, entry_point, paymaster, block_number, log_index, change, amount, seconds) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+             ON CONFLICT DO NOTHING"
+        )
+        .bind(event.chain_id as i32)
+        .bind(&event.entry_point)
+        .bind(&event.paymaster)
+        .bind(event.block_number as i64)
+        .bind(event.log_index as i64)
+        .bind(change)
+        .bind(amount)
+        .bind(seconds)
+        .execute(&mut *tx)
+        .await?;
+
+        recompute_deposit(&mut tx, event.chain_id, &event.entry_point, &event.paymaster).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn reconcile_deposit(
+        &self,
+        chain_id: u32,
+        entry_point: &str,
+        paymaster: &str,
+        deposit: BigDecimal,
+        block_number: u64,
+    ) -> Result<Option<BigDecimal>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let previous = lock_deposit_row(&mut tx, chain_id, entry_point, paymaster).await?;
+
+        // Every log of `block_number` is covered by the balance, hence the max log index
+        sqlx::query(
+            "INSERT INTO paymaster_deposit_events \
+             (chain_id, entry_point, paymaster, block_number, log_index, change, amount) \
+             VALUES ($1, $2, $3, $4, $5, 'RECONCILED', $6) \
+             ON CONFLICT (chain_id, entry_point, paymaster, block_number, log_index, change) DO UPDATE \
+             SET amount = EXCLUDED.amount"
+        )
+        .bind(chain_id as i32)
+        .bind(entry_point)
+        .bind(paymaster)
+        .bind(block_number as i64)
+        .bind(i64::MAX)
+        .bind(&deposit)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE paymaster_deposits SET reconciled_at = NOW() \
+             WHERE chain_id = $1 AND entry_point = $2 AND paymaster = $3"
+        )
+        .bind(chain_id as i32)
+        .bind(entry_point)
+        .bind(paymaster)
+        .execute(&mut *tx)
+        .await?;
+
+        recompute_deposit(&mut tx, chain_id, entry_point, paymaster).await?;
+        tx.commit().await?;
+        Ok(previous)
+    }
+
+    async fn record_dead_letter(&self, letter: DeadLetter) -> Result<i32, Error> {
+        tracing::info!("🟠 Dead-lettering {} {} at stage {}", letter.source.as_str(), letter.entry_key, letter.stage.as_str());
+
+        let attempts: i32 = sqlx::query_scalar(
+            "INSERT INTO dead_letters \
+             (source, entry_key, chain_id, block_number, log_index, stage, error, payload) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+             ON CONFLICT (source, entry_key) DO UPDATE \
+             SET stage = EXCLUDED.stage, error = EXCLUDED.error, payload = EXCLUDED.payload,\
+                 attempts = dead_letters.attempts + 1, last_failed_at = NOW(), resolved_at = NULL \
+             RETURNING attempts"
+        )
+        .bind(letter.source.as_str())
+        .bind(&letter.entry_key)
+        .bind(letter.chain_id.map(|c| c as i32))
+        .bind(letter.block_number.map(|b| b as i64))
+        .bind(letter.log_index.map(|i| i as i32))
+        .bind(letter.stage.as_str())
+        .bind(&letter.error)
+        .bind(&letter.payload)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(attempts)
+    }
+
+    async fn get_dead_letters(&self, source: Option<DeadLetterSource>, limit: i64) -> Result<Vec<DeadLetterRecord>, Error> {
+        let rows: Vec<(i64, String, String, String, i32)> = sqlx::query_as(
+            "SELECT id, source, entry_key, payload, attempts FROM dead_letters \
+             WHERE resolved_at IS NULL AND ($1::VARCHAR IS NULL OR source = $1) \
+             ORDER BY source, chain_id, block_number, log_index, id \
+             LIMIT $2"
+        )
+        .bind(source.map(|s| s.as_str()))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, source, entry_key, payload, attempts)| DeadLetterRecord { id, source, entry_key, payload, attempts })
+            .collect())
+    }
+
+    async fn resolve_dead_letter(&self, id: i64) -> Result<(), Error> {
+        sqlx::query("UPDATE dead_letters SET resolved_at = NOW() WHERE id = $1")
+            .bind(id)
             .execute(&self.pool)
             .await?;
-        }
+        Ok(())
+    }
 
+    async fn resolve_dead_letter_key(&self, source: DeadLetterSource, entry_key: &str) -> Result<(), Error> {
+        sqlx::query(
+            "UPDATE dead_letters SET resolved_at = NOW() \
+             WHERE source = $1 AND entry_key = $2 AND resolved_at IS NULL"
+        )
+        .bind(source.as_str())
+        .bind(entry_key)
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
+
+    async fn fail_dead_letter(&self, id: i64, error: &str) -> Result<(), Error> {
+        sqlx::query(
+            "UPDATE dead_letters SET error = $2, attempts = attempts + 1, last_failed_at = NOW() WHERE id = $1"
+        )
+        .bind(id)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn refresh_spend_aggregates(&self) -> Result<(), Error> {
+        // Only invalidated or never materialized buckets are recomputed, e.g. after a backfill of old blocks.
+        // The daily aggregate reads the hourly one, so it goes second.
+        for view in ["user_op_spend_hourly", "user_op_spend_daily"] {
+            // Unprepared, refreshing cannot run inside a transaction block
+            self.pool
+                .execute(format!("CALL refresh_continuous_aggregate('{}', NULL, NULL)", view).as_str())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_spend(&self, query: SpendQuery) -> Result<Vec<SpendBucket>, Error> {
+        let sql = format!(
+            "SELECT bucket, chain_id, paymaster_mode, paymaster_id, org_id, policy_id, ops, failed_ops,\
+                    gas_used, native_cost_wei, usd_amount \
+             FROM {} \
+             WHERE bucket >= $1 AND bucket < $2 \
+               AND ($3::INTEGER IS NULL OR chain_id = $3) \
+               AND ($4::TEXT IS NULL OR paymaster_mode = $4) \
+               AND ($5::TEXT IS NULL OR paymaster_id = $5) \
+               AND ($6::TEXT IS NULL OR org_id = $6) \
+               AND ($7::TEXT IS NULL OR policy_id = $7) \
+             ORDER BY bucket, chain_id",
+            query.interval.view()
+        );
+        let buckets = sqlx::query_as::<_, SpendBucket>(&sql)
+            .bind(query.from)
+            .bind(query.to)
+            .bind(query.chain_id.map(|c| c as i32))
+            .bind(&query.paymaster_mode)
+            .bind(&query.paymaster_id)
+            .bind(&query.org_id)
+            .bind(&query.policy_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(buckets)
+    }
+
+    async fn get_checkpoint(&self, chain_id: u32, cursor: &str) -> Result<Option<u64>, Error> {
+        let block_number: Option<i64> = sqlx::query_scalar(
+            "SELECT block_number FROM indexer_checkpoints WHERE chain_id = $1 AND cursor = $2"
+        )
+        .bind(chain_id as i32)
+        .bind(cursor)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(block_number.map(|b| b as u64))
+    }
+
+    async fn set_checkpoint(&self, chain_id: u32, cursor: &str, block_number: u64, user_ops: Vec<UserOpMessage>) -> Result<(), Error> {
+        let checkpoint = Checkpoint { chain_id, cursor, block_number };
+        write_batch(&self.pool, &user_ops, Some(&checkpoint)).await
+    }
+
+    async fn rollback_from_block(&self, chain_id: u32, from_block: u64) -> Result<Vec<String>, Error> {
+        let chain_id = chain_id as i32;
+        let from_block = from_block as i64;
+        let mut tx = self.pool.begin().await?;
+
+        // Rows only the indexer knows about are removed, they are re-created if the op lands again
+        let mut rolled_back: Vec<String> = sqlx::query_scalar(
+            "DELETE FROM pm_user_operations \
+             WHERE chain_id = $1 AND block_number >= $2 AND org_id IS NULL AND paymaster_id IS NULL \
+             RETURNING user_op_hash"
+        )
+        .bind(chain_id)
+        .bind(from_block)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // The op gets the time of its next sighting if it lands again
+        sqlx::query("DELETE FROM user_op_keys WHERE chain_id = $1 AND user_op_hash = ANY($2)")
+            .bind(chain_id)
+            .bind(&rolled_back)
+            .execute(&mut *tx)
+            .await?;
+
+        // Rows that came from the paymaster service keep their ownership data and go back to eligible
+        let reset: Vec<String> = sqlx::query_scalar(
+            "UPDATE pm_user_operations \
+             SET status = $3, block_number = NULL, block_hash = NULL,\
+                 tx_hash = NULL, log_index = NULL, bundler = NULL, beneficiary = NULL, effective_gas_price = NULL,\
+                 actual_gas_cost = NULL, actual_gas_used = NULL, usd_amount = NULL, account_deployed = NULL \
+             WHERE chain_id = $1 AND block_number >= $2 \
+             RETURNING user_op_hash"
+        )
+        .bind(chain_id)
+        .bind(from_block)
+        .bind(Status::Eligible.to_string())
+        .fetch_all(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM smart_accounts WHERE chain_id = $1 AND first_seen_block >= $2")
+            .bind(chain_id)
+            .bind(from_block)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM user_op_revert_reasons WHERE chain_id = $1 AND block_number >= $2")
+            .bind(chain_id)
+            .bind(from_block)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM contract_events WHERE chain_id = $1 AND block_number >= $2")
+            .bind(chain_id)
+            .bind(from_block)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM user_op_sponsorships WHERE chain_id = $1 AND block_number >= $2")
+            .bind(chain_id)
+            .bind(from_block)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM paymaster_refunds WHERE chain_id = $1 AND block_number >= $2")
+            .bind(chain_id)
+            .bind(from_block)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM prepaid_gas_ledger WHERE chain_id = $1 AND block_number >= $2")
+            .bind(chain_id)
+            .bind(from_block)
+            .execute(&mut *tx)
+            .await?;
+
+        // Deposits are recomputed without the orphaned changes, balanceOf checks of orphaned blocks included
+        let deposits: Vec<(String, String)> = sqlx::query_as(
+            "DELETE FROM paymaster_deposit_events WHERE chain_id = $1 AND block_number >= $2 \
+             RETURNING entry_point, paymaster"
+        )
+        .bind(chain_id)
+        .bind(from_block)
+        .fetch_all(&mut *tx)
+        .await?;
+        let deposits: BTreeSet<(String, String)> = deposits.into_iter().collect();
+        for (entry_point, paymaster) in &deposits {
+            recompute_deposit(&mut tx, chain_id as u32, entry_point, paymaster).await?;
+        }
+
+        // Logs of orphaned blocks must not be replayed
+        sqlx::query(
+            "DELETE FROM dead_letters \
+             WHERE source = 'log' AND resolved_at IS NULL AND chain_id = $1 AND block_number >= $2"
+        )
+        .bind(chain_id)
+        .bind(from_block)
+        .execute(&mut *tx)
+        .await?;
+
+        // Rewound with the rows, so a crash cannot leave the checkpoint past data that is gone
+        sqlx::query("UPDATE indexer_checkpoints SET block_number = $2 - 1 WHERE chain_id = $1 AND block_number >= $2")
+            .bind(chain_id)
+            .bind(from_block)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        rolled_back.extend(reset);
+        Ok(rolled_back.into_iter().map(|h| h.trim().to_string()).collect())
+    }
+}
+
+/// **Create the deposit row if needed and lock it, returning the current deposit**
+///
+/// Changes of one row are serialized, so every recompute sees the events committed before it.
+async fn lock_deposit_row(
+    tx: &mut Transaction<'_, Postgres>,
+    chain_id: u32,
+    entry_point: &str,
+    paymaster: &str,
+) -> Result<Option<BigDecimal>, Error> {
+    sqlx::query(
+        "INSERT INTO paymaster_deposits (chain_id, entry_point, paymaster) VALUES ($1, $2, $3) \
+         ON CONFLICT (chain_id, entry_point, paymaster) DO NOTHING"
+    )
+    .bind(chain_id as i32)
+    .bind(entry_point)
+    .bind(paymaster)
+    .execute(&mut **tx)
+    .await?;
+
+    let deposit: Option<BigDecimal> = sqlx::query_scalar(
+        "SELECT deposit FROM paymaster_deposits \
+         WHERE chain_id = $1 AND entry_point = $2 AND paymaster = $3 \
+         FOR UPDATE"
+    )
+    .bind(chain_id as i32)
+    .bind(entry_point)
+    .bind(paymaster)
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(deposit)
+}
+
+/// **Recompute a paymaster's deposit and stake from its stored changes**
+///
+/// The deposit is the newest absolute value (a Deposited total or a balanceOf check) minus the
+/// withdrawals and gas charges after it, so the order changes arrive in does not matter.
+async fn recompute_deposit(
+    tx: &mut Transaction<'_, Postgres>,
+    chain_id: u32,
+    entry_point: &str,
+    paymaster: &str,
+) -> Result<(), Error> {
+    sqlx::query(
+        "WITH e AS (\
+             SELECT * FROM paymaster_deposit_events \
+             WHERE chain_id = $1 AND entry_point = $2 AND paymaster = $3\
+         ), anchor AS (\
+             SELECT amount, block_number, log_index FROM e \
+             WHERE change IN ('DEPOSITED', 'RECONCILED') \
+             ORDER BY block_number DESC, log_index DESC LIMIT 1\
+         ), stake_change AS (\
+             SELECT change, amount, seconds FROM e \
+             WHERE change IN ('STAKE_LOCKED', 'STAKE_WITHDRAWN') \
+             ORDER BY block_number DESC, log_index DESC, change DESC LIMIT 1\
+         ), unlock_change AS (\
+             SELECT change, seconds FROM e \
+             WHERE change IN ('STAKE_LOCKED', 'STAKE_UNLOCKED', 'STAKE_WITHDRAWN') \
+             ORDER BY block_number DESC, log_index DESC, change DESC LIMIT 1\
+         ), last_change AS (\
+             SELECT block_number, log_index FROM e ORDER BY block_number DESC, log_index DESC LIMIT 1\
+         ) \
+         UPDATE paymaster_deposits SET \
+             deposit = (\
+                 SELECT a.amount - COALESCE(SUM(e.amount), 0) FROM anchor a \
+                 LEFT JOIN e ON e.change IN ('WITHDRAWN', 'GAS_CHARGED') \
+                            AND (e.block_number, e.log_index) > (a.block_number, a.log_index) \
+                 GROUP BY a.amount\
+             ),\
+             stake = (SELECT CASE WHEN change = 'STAKE_LOCKED' THEN amount ELSE 0 END FROM stake_change),\
+             unstake_delay_sec = (SELECT CASE WHEN change = 'STAKE_LOCKED' THEN seconds ELSE 0 END FROM stake_change),\
+             withdraw_time = (SELECT CASE WHEN change = 'STAKE_UNLOCKED' THEN seconds ELSE 0 END FROM unlock_change),\
+             last_block = COALESCE((SELECT block_number FROM last_change), 0),\
+             last_log_index = COALESCE((SELECT log_index FROM last_change), 0) \
+         WHERE chain_id = $1 AND entry_point = $2 AND paymaster = $3"
+    )
+    .bind(chain_id as i32)
+    .bind(entry_point)
+    .bind(paymaster)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
 }
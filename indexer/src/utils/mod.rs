@@ -1,54 +1,71 @@
+use alloy::primitives::U256;
 use sqlx::types::BigDecimal;
 use std::str::FromStr;
 use serde_json::Value;
 
-// Converts gas cost string + native token USD price into a `f64` USD amount
-pub fn calculate_usd_spent(actual_gas_cost: &str, native_usd_price: &str) -> Option<f64> {
-    let cost_wei = if actual_gas_cost.starts_with("0x") {
-        u64::from_str_radix(actual_gas_cost.trim_start_matches("0x"), 16).ok()? as f64
-    } else {
-        actual_gas_cost.parse::<f64>().ok()?
-    };
+// Wei amount as a decimal or 0x-prefixed hex string, None when it is not a valid uint256
+pub fn parse_wei(value: &str) -> Option<U256> {
+    U256::from_str(value.trim()).ok()
+}
 
-    let usd_price = native_usd_price.parse::<f64>().ok()?;
-    Some(cost_wei * usd_price / 1e18)
+// Native token amount of a wei amount, exact: 1 wei is 10^-18
+pub fn wei_to_native(wei: &BigDecimal) -> BigDecimal {
+    wei * BigDecimal::new(1.into(), 18)
 }
 
-pub fn parse_gas_value(val: Option<&String>) -> u64 {
-    match val {
-        Some(s) if s.starts_with("0x") => u64::from_str_radix(s.trim_start_matches("0x"), 16).unwrap_or(0),
-        Some(s) => s.parse::<u64>().unwrap_or(0),
-        None => 0,
-    }
+// Converts gas cost string (wei) + native token USD price into an exact USD amount
+pub fn calculate_usd_spent(actual_gas_cost: &str, native_usd_price: &str) -> Option<BigDecimal> {
+    let cost_wei = u256_to_decimal(parse_wei(actual_gas_cost)?);
+    let usd_price = BigDecimal::from_str(native_usd_price.trim()).ok()?;
+    Some(wei_to_native(&cost_wei) * usd_price)
 }
 
-pub fn extract_meta_fields(meta: &serde_json::Map<String, Value>) -> (
-    Option<i64>,               // actualGasCost
-    Option<i64>,               // actualGasUsed
-    Option<String>,            // deductedUser
-    Option<BigDecimal>,        // deductedAmount
-    Option<String>,            // token
-    Option<BigDecimal>,        // premium
-    Option<BigDecimal>,        // tokenCharge
-    Option<BigDecimal>,        // appliedMarkup
-    Option<BigDecimal>         // exchangeRate
-) {
+// Exact decimal form of an on-chain integer
+pub fn u256_to_decimal(value: U256) -> BigDecimal {
+    BigDecimal::from_str(&value.to_string()).expect("U256 is a valid decimal")
+}
+
+/// Typed columns extracted from the `metadata` JSON of a user op
+#[derive(Debug, Default)]
+pub struct MetaFields {
+    pub actual_gas_cost: Option<BigDecimal>,
+    pub actual_gas_used: Option<BigDecimal>,
+    pub deducted_user: Option<String>,
+    pub deducted_amount: Option<BigDecimal>,
+    pub token: Option<String>,
+    pub premium: Option<BigDecimal>,
+    pub token_charge: Option<BigDecimal>,
+    pub applied_markup: Option<BigDecimal>,
+    pub exchange_rate: Option<BigDecimal>,
+    pub tx_hash: Option<String>,
+    pub log_index: Option<i32>,
+    pub bundler: Option<String>,
+    pub beneficiary: Option<String>,
+    pub effective_gas_price: Option<BigDecimal>,
+}
+
+pub fn extract_meta_fields(meta: &serde_json::Map<String, Value>) -> MetaFields {
     let get_str = |key: &str| meta.get(key).and_then(|v| v.as_str());
-    let parse_i64 = |key: &str| get_str(key).and_then(|s| s.parse::<i64>().ok());
+    let parse_wei_decimal = |key: &str| get_str(key).and_then(parse_wei).map(u256_to_decimal);
     let parse_str = |key: &str| get_str(key).map(|s| s.to_string());
     let parse_decimal = |key: &str| get_str(key).and_then(|s| BigDecimal::from_str(s).ok());
 
-    (
-        parse_i64("actualGasCost"),
-        parse_i64("actualGasUsed"),
-        parse_str("deductedUser"),
-        parse_decimal("deductedAmount"),
-        parse_str("token"),
-        parse_decimal("premium"),
-        parse_decimal("tokenCharge"),
-        parse_decimal("appliedMarkup"),
-        parse_decimal("exchangeRate"),
-    )
+    MetaFields {
+        actual_gas_cost: parse_wei_decimal("actualGasCost"),
+        actual_gas_used: parse_wei_decimal("actualGasUsed"),
+        deducted_user: parse_str("deductedUser"),
+        deducted_amount: parse_decimal("deductedAmount"),
+        token: parse_str("token"),
+        premium: parse_decimal("premium"),
+        token_charge: parse_decimal("tokenCharge"),
+        applied_markup: parse_decimal("appliedMarkup"),
+        exchange_rate: parse_decimal("exchangeRate"),
+        tx_hash: parse_str("transactionHash"),
+        log_index: get_str("logIndex").and_then(|s| s.parse::<i32>().ok()),
+        bundler: parse_str("bundler"),
+        beneficiary: parse_str("beneficiary"),
+        effective_gas_price: parse_decimal("effectiveGasPrice"),
+    }
 }
 
 pub fn append_usage_update_cmds(
@@ -57,19 +74,19 @@ pub fn append_usage_update_cmds(
     policy_id: &str,
     user: Option<&str>,
     gas: u64,
-    usd_spent: f64,
-    gas_cost_eth: f64,
+    usd_spent: &BigDecimal,
+    gas_cost_eth: &BigDecimal,
 ) {
     let prefix = match user {
         Some(u) => format!("{}:{}:{}", scope, policy_id, u),
         None => format!("{}:{}", scope, policy_id),
     };
 
-    // Increment confirmed usage counters
+    // Increment confirmed usage counters, amounts are sent exactly and Redis keeps them as long doubles
     pipe.cmd("INCRBY").arg(format!("{}:ops", &prefix)).arg(1)
         .cmd("INCRBY").arg(format!("{}:gas", &prefix)).arg(gas)
-        .cmd("INCRBYFLOAT").arg(format!("{}:usd", &prefix)).arg(format!("{:.6}", usd_spent))
-        .cmd("INCRBYFLOAT").arg(format!("{}:eth", &prefix)).arg(format!("{:.12}", gas_cost_eth));
+        .cmd("INCRBYFLOAT").arg(format!("{}:usd", &prefix)).arg(usd_spent.to_string())
+        .cmd("INCRBYFLOAT").arg(format!("{}:eth", &prefix)).arg(gas_cost_eth.to_string());
 
     // Delete pending buffer keys
     pipe.cmd("DEL")
@@ -78,3 +95,24 @@ pub fn append_usage_update_cmds(
         .arg(format!("{}:pending_usd", &prefix))
         .arg(format!("{}:pending_eth", &prefix));
 }
+
+pub fn append_usage_revert_cmds(
+    pipe: &mut redis::Pipeline,
+    scope: &str,
+    policy_id: &str,
+    user: Option<&str>,
+    gas: u64,
+    usd_spent: &BigDecimal,
+    gas_cost_eth: &BigDecimal,
+) {
+    let prefix = match user {
+        Some(u) => format!("{}:{}:{}", scope, policy_id, u),
+        None => format!("{}:{}", scope, policy_id),
+    };
+
+    // Take back confirmed usage counted for a reorged user op
+    pipe.cmd("DECRBY").arg(format!("{}:ops", &prefix)).arg(1)
+        .cmd("DECRBY").arg(format!("{}:gas", &prefix)).arg(gas)
+        .cmd("INCRBYFLOAT").arg(format!("{}:usd", &prefix)).arg((-usd_spent.clone()).to_string())
+        .cmd("INCRBYFLOAT").arg(format!("{}:eth", &prefix)).arg((-gas_cost_eth.clone()).to_string());
+}